@@ -0,0 +1,219 @@
+//! A blocking client, used by the `dump!` macro.
+
+use std::{
+    env, fmt,
+    sync::{
+        mpsc::{self, SyncSender},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::protocol::{Breakpoint, Callstack};
+
+// Where the client sends breakpoints when `PMD_URL` isn't set.
+const DEFAULT_URL: &str = "http://127.0.0.1:6969";
+
+// How many frames of the backtrace are sent as the callstack.
+const MAX_FRAMES: usize = 50;
+
+// How many breakpoints the background sender buffers before dropping them.
+const BACKGROUND_CAPACITY: usize = 1024;
+
+// Name of the background sender thread, events logged on it aren't forwarded.
+const BACKGROUND_THREAD: &str = "pmd-sender";
+
+/// Sends breakpoints to a running pmd.
+#[derive(Debug, Clone)]
+pub struct Client {
+    url: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    // The server refused the breakpoint.
+    Rejected { status: u16, reason: String },
+    // The server couldn't be reached.
+    Transport(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Rejected { status, reason } => write!(f, "pmd rejected the breakpoint ({}): {}", status, reason),
+            Error::Transport(err) => write!(f, "could not reach pmd: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Client {
+    /// Creates a client for the server at `url`, e.g. `http://127.0.0.1:6969`.
+    pub fn new(url: impl Into<String>) -> Client {
+        // A missing pmd shouldn't hold up the program for long.
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(1))
+            .build();
+
+        Client {
+            url: url.into().trim_end_matches('/').to_string(),
+            token: None,
+            agent,
+        }
+    }
+
+    /// Sends the bearer token the server is configured with.
+    pub fn token(mut self, token: impl Into<String>) -> Client {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Creates a client from the `PMD_URL` and `PMD_TOKEN` environment variables.
+    pub fn from_env() -> Client {
+        let client = Client::new(env::var("PMD_URL").unwrap_or_else(|_| DEFAULT_URL.to_string()));
+
+        match env::var("PMD_TOKEN") {
+            Ok(token) if !token.is_empty() => client.token(token),
+            _ => client,
+        }
+    }
+
+    /// Sends the breakpoint and returns the id the server assigned to it.
+    pub fn send(&self, breakpoint: &Breakpoint) -> Result<u64, Error> {
+        let mut request = self.agent.post(&format!("{}/dump", self.url));
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+
+        let body = serde_json::to_string(breakpoint).expect("Could not serialize the breakpoint.");
+        match request.set("Content-Type", "application/json").send_string(&body) {
+            Ok(response) => {
+                let body = response
+                    .into_string()
+                    .map_err(|err| Error::Transport(err.to_string()))?;
+                let body: Value = serde_json::from_str(&body).unwrap_or_default();
+                Ok(body["id"].as_u64().unwrap_or_default())
+            }
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                let body: Value = serde_json::from_str(&body).unwrap_or_default();
+                let reason = body["error"]["reason"].as_str().unwrap_or_default().to_string();
+                Err(Error::Rejected { status, reason })
+            }
+            Err(err) => Err(Error::Transport(err.to_string())),
+        }
+    }
+}
+
+/// Sends breakpoints from a background thread, so logging never waits on
+/// the server. Breakpoints are dropped when the server can't keep up, and
+/// the ones still buffered when the program exits are lost.
+#[derive(Debug, Clone)]
+pub struct Background {
+    tx: SyncSender<Breakpoint>,
+}
+
+impl Background {
+    pub fn new(client: Client) -> Background {
+        let (tx, rx) = mpsc::sync_channel::<Breakpoint>(BACKGROUND_CAPACITY);
+
+        thread::Builder::new()
+            .name(BACKGROUND_THREAD.to_string())
+            .spawn(move || {
+                let mut reported = false;
+                for breakpoint in rx {
+                    // Report the first error only, pmd is most likely not running.
+                    if let Err(err) = client.send(&breakpoint) {
+                        if !reported {
+                            eprintln!("pmd: {}", err);
+                            reported = true;
+                        }
+                    }
+                }
+            })
+            .expect("Could not spawn the pmd sender thread.");
+
+        Background { tx }
+    }
+
+    pub fn send(&self, breakpoint: Breakpoint) {
+        let _ = self.tx.try_send(breakpoint);
+    }
+}
+
+/// Whether a log event should be forwarded. Events of the sender itself, and
+/// of the HTTP client it uses, would otherwise loop back to the server.
+#[cfg(any(feature = "tracing", feature = "log"))]
+pub(crate) fn should_forward(target: &str) -> bool {
+    let own = ["ureq", "rustls"]
+        .iter()
+        .any(|prefix| target == *prefix || target.starts_with(&format!("{}::", prefix)));
+
+    !own && thread::current().name() != Some(BACKGROUND_THREAD)
+}
+
+/// The client used by the `dump!` macro, configured from the environment.
+pub fn global() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(Client::from_env)
+}
+
+/// Returns the callstack of the caller, without the frames of the standard
+/// library, dependencies and pmd itself. Needs debug info to resolve files.
+pub fn callstack() -> Vec<Callstack> {
+    let backtrace = backtrace::Backtrace::new();
+
+    backtrace
+        .frames()
+        .iter()
+        .flat_map(|frame| frame.symbols())
+        .filter(|symbol| {
+            let name = symbol.name().map(|name| name.to_string()).unwrap_or_default();
+            !name.starts_with("backtrace::") && !name.starts_with("pmd::client::")
+        })
+        .filter_map(|symbol| {
+            let filepath = symbol.filename()?.to_string_lossy().to_string();
+            let line = symbol.lineno()?;
+
+            let external = filepath.starts_with("/rustc/")
+                || filepath.contains("/.cargo/registry/")
+                || filepath.contains("/rustlib/");
+            if external {
+                return None;
+            }
+
+            Some(Callstack {
+                filepath,
+                line: line.to_string(),
+            })
+        })
+        .take(MAX_FRAMES)
+        .collect()
+}
+
+#[doc(hidden)]
+pub fn to_payload<T: Serialize + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or_else(|err| Value::String(format!("Could not serialize the value: {}", err)))
+}
+
+#[doc(hidden)]
+pub fn dump(file: &str, line: u32, values: Vec<(&str, Value)>) {
+    let payload: Map<String, Value> = values
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+
+    let mut breakpoint = Breakpoint::new(file, line.to_string(), "rust", Value::Object(payload));
+    breakpoint.callstack = callstack();
+
+    if let Err(err) = global().send(&breakpoint) {
+        eprintln!("pmd: {}", err);
+    }
+}
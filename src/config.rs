@@ -1,17 +1,118 @@
 use std::{
     fs::{self, read_to_string},
+    net::{IpAddr, Ipv4Addr},
     path::PathBuf,
 };
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use home::home_dir;
 use rust_embed::RustEmbed;
 use serde::Deserialize;
 
 #[derive(Debug)]
 pub struct Config {
-    pub port: u16,
+    pub server: ServerConfig,
+    pub dbgp: DbgpConfig,
     pub keymap: Keymap,
+    pub editor: EditorConfig,
+    // Rewrites the paths of adapters running elsewhere, e.g. in containers.
+    pub path_mappings: Vec<PathMapping>,
+    pub session: Option<String>,
+    pub mode: Mode,
+    // Command started by `pmd run`, whose stderr is turned into breakpoints.
+    pub run: Option<Vec<String>>,
+}
+
+/// The front end that presents the breakpoints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Tui,
+    // Debug Adapter Protocol, over stdio or on a TCP port.
+    Dap { port: Option<u16> },
+    // Prints the breakpoints to stdout, as text or NDJSON.
+    Tail { json: bool },
+    // Sends a single breakpoint to a running pmd and exits.
+    Send(SendOptions),
+}
+
+/// A breakpoint given on the command line, for shell scripts.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct SendOptions {
+    /// File the breakpoint is in.
+    #[arg(short, long, required_unless_present = "shell_stack")]
+    pub file: Option<String>,
+
+    /// Line the breakpoint is on.
+    #[arg(short, long, required_unless_present = "shell_stack")]
+    pub line: Option<String>,
+
+    /// JSON payload, plain text is sent as a string. `-` reads it from stdin.
+    #[arg(long, value_name = "JSON")]
+    pub payload: Option<String>,
+
+    /// Adds a callstack frame, can be repeated, e.g. `--frame "${BASH_SOURCE[1]}:${BASH_LINENO[0]}"`.
+    #[arg(long = "frame", value_name = "FILE:LINE")]
+    pub frames: Vec<String>,
+
+    /// Reads the caller's stack from `PMD_BASH_SOURCE` and `PMD_BASH_LINENO`, as exported by
+    /// the `pmd_dump` helper in the README. Its top frame is the file and line when not given.
+    #[arg(long)]
+    pub shell_stack: bool,
+
+    /// Connector the breakpoint is shown with.
+    #[arg(long, default_value = "shell")]
+    pub connector_type: String,
+
+    /// Waits until the breakpoint is continued in the UI.
+    #[arg(long)]
+    pub pause: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind: IpAddr,
+    pub port: u16,
+    pub max_breakpoints: usize,
+    // Seconds a paused breakpoint waits for the user to continue.
+    pub pause_timeout: u64,
+    // Unix domain socket to listen on, alongside the TCP port.
+    pub socket: Option<PathBuf>,
+    // When set, dump requests need an `Authorization: Bearer <token>` header.
+    pub token: Option<String>,
+}
+
+impl ServerConfig {
+    pub fn new() -> ServerConfig {
+        ServerConfig {
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 6969,
+            max_breakpoints: 10000,
+            pause_timeout: 300,
+            socket: None,
+            token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbgpConfig {
+    pub enabled: bool,
+    pub bind: IpAddr,
+    pub port: u16,
+    // Break on the first line of every script, instead of running until
+    // the first `xdebug_break()`.
+    pub break_at_first_line: bool,
+}
+
+impl DbgpConfig {
+    pub fn new() -> DbgpConfig {
+        DbgpConfig {
+            enabled: false,
+            bind: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 9003,
+            break_at_first_line: false,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -28,21 +129,101 @@ impl Keymap {
 impl Config {
     pub fn new() -> Config {
         Config {
-            port: 6969,
+            server: ServerConfig::new(),
+            dbgp: DbgpConfig::new(),
             keymap: Keymap::new(),
+            editor: EditorConfig::new(),
+            path_mappings: vec![],
+            session: None,
+            mode: Mode::Tui,
+            run: None,
+        }
+    }
+}
+
+/// How the selected breakpoint is opened in an editor.
+#[derive(Debug, Clone)]
+pub struct EditorConfig {
+    // Template with `{file}` and `{line}`, falls back to `$VISUAL` or
+    // `$EDITOR` when not set.
+    pub command: Option<String>,
+    pub mode: EditorMode,
+}
+
+impl EditorConfig {
+    pub fn new() -> EditorConfig {
+        EditorConfig {
+            command: None,
+            mode: EditorMode::Suspend,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EditorMode {
+    // Hands the terminal over to the editor until it exits.
+    Suspend,
+    // Opens the editor in a new tmux pane.
+    Tmux,
+    // Starts the editor without waiting for it, for GUI editors.
+    Background,
+}
+
+/// Maps paths under `remote`, as sent by the adapters, to the same files
+/// under `local`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PathMapping {
+    pub remote: String,
+    pub local: String,
+    // Only applies to breakpoints of this connector, when set.
+    pub connector_type: Option<String>,
+}
+
+impl PathMapping {
+    /// Returns the local path, or `None` when the path isn't under `remote`
+    /// or the breakpoint is from another connector.
+    pub fn map(&self, connector_type: &str, path: &str) -> Option<String> {
+        if self.connector_type.as_deref().is_some_and(|only| only != connector_type) {
+            return None;
+        }
+
+        let remote = self.remote.trim_end_matches('/');
+        let rest = path.strip_prefix(remote)?;
+        // "/var/www" shouldn't match "/var/www2".
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+
+        Some(format!("{}{}", self.local.trim_end_matches('/'), rest))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TomlConfig {
     server: Option<ServerTomlConfig>,
+    dbgp: Option<DbgpTomlConfig>,
     keymap: Option<KeymapTomlConfig>,
+    editor: Option<EditorTomlConfig>,
+    path_mappings: Option<Vec<PathMapping>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct ServerTomlConfig {
+    bind: Option<IpAddr>,
+    port: Option<u16>,
+    max_breakpoints: Option<usize>,
+    pause_timeout: Option<u64>,
+    socket: Option<PathBuf>,
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DbgpTomlConfig {
+    enabled: Option<bool>,
+    bind: Option<IpAddr>,
     port: Option<u16>,
+    break_at_first_line: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,6 +231,12 @@ struct KeymapTomlConfig {
     leader_key: Option<char>,
 }
 
+#[derive(Debug, Deserialize)]
+struct EditorTomlConfig {
+    command: Option<String>,
+    mode: Option<EditorMode>,
+}
+
 #[derive(RustEmbed)]
 #[folder = "stubs/"]
 struct Stub;
@@ -58,12 +245,55 @@ struct Stub;
 #[command(author, version, about, long_about = None)]
 pub struct PmdCli {
     /// Sets a custom config file
-    #[arg(short, long, value_name = "FILE")]
+    #[arg(short, long, value_name = "FILE", global = true)]
     config: Option<PathBuf>,
 
     /// Sets a listening port.
-    #[arg(short, long, value_name = "PORT")]
+    #[arg(short, long, value_name = "PORT", global = true)]
     port: Option<u16>,
+
+    /// Sets the address to listen on, 127.0.0.1 by default or e.g. 0.0.0.0 for all interfaces
+    #[arg(short, long, value_name = "ADDRESS", global = true)]
+    bind: Option<IpAddr>,
+
+    /// Listens for DBGp (Xdebug) debugger engines.
+    #[arg(long, global = true)]
+    dbgp: bool,
+
+    /// Reopens a previous session, or starts a new one with the given name.
+    #[arg(short, long, value_name = "NAME", global = true)]
+    session: Option<String>,
+
+    /// Prints the breakpoints to stdout instead of starting the TUI, same as `pmd tail`.
+    #[arg(long, global = true)]
+    headless: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Exposes the breakpoints over the Debug Adapter Protocol, instead of the TUI.
+    Dap {
+        /// Listens for a DAP client on this port, instead of using stdio.
+        #[arg(long, value_name = "PORT")]
+        listen: Option<u16>,
+    },
+    /// Runs a command and turns its `dbg!` output and panics into breakpoints.
+    Run {
+        /// The command and its arguments, after `--`.
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+    /// Sends a breakpoint to a running pmd.
+    Send(SendOptions),
+    /// Prints the breakpoints to stdout as they arrive, instead of the TUI.
+    Tail {
+        /// Prints every breakpoint as a JSON line.
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 pub fn parse(config: &mut Config) {
@@ -77,17 +307,40 @@ pub fn parse(config: &mut Config) {
 
     // Check if we pass the config file at runtime
     // otherwise try to load it from the default locations.
-    if cli.config.is_some() {
-        let config_path = cli.config.expect("Missing config path.");
+    if let Some(config_path) = cli.config {
         parse_toml_config(config, config_path);
     } else {
         parse_toml_config(config, config_file_path);
     }
 
     // cmd line args have higher prio.
-    if cli.port.is_some() {
-        config.port = cli.port.unwrap();
+    if let Some(port) = cli.port {
+        config.server.port = port;
     }
+    if let Some(bind) = cli.bind {
+        config.server.bind = bind;
+    }
+    if cli.dbgp {
+        config.dbgp.enabled = true;
+    }
+
+    config.session = cli.session;
+
+    config.mode = match cli.command {
+        None if cli.headless => Mode::Tail { json: false },
+        None => Mode::Tui,
+        Some(Commands::Run { command }) => {
+            config.run = Some(command);
+            if cli.headless {
+                Mode::Tail { json: false }
+            } else {
+                Mode::Tui
+            }
+        }
+        Some(Commands::Tail { json }) => Mode::Tail { json },
+        Some(Commands::Send(options)) => Mode::Send(options),
+        Some(Commands::Dap { listen }) => Mode::Dap { port: listen },
+    };
 }
 
 fn parse_toml_config(config: &mut Config, path: PathBuf) {
@@ -97,25 +350,75 @@ fn parse_toml_config(config: &mut Config, path: PathBuf) {
         toml::from_str(&toml_content).expect("Can't parse the toml config file.");
 
     // server config
-    if toml_config.server.is_some() {
-        let server_config = toml_config.server.unwrap();
-        if server_config.port.is_some() {
-            let server_port_config = server_config.port.unwrap();
-            config.port = server_port_config;
+    if let Some(server_config) = toml_config.server {
+        if let Some(server_bind_config) = server_config.bind {
+            config.server.bind = server_bind_config;
+        }
+        if let Some(server_port_config) = server_config.port {
+            config.server.port = server_port_config;
+        }
+        if let Some(max_breakpoints_config) = server_config.max_breakpoints {
+            config.server.max_breakpoints = max_breakpoints_config;
+        }
+        if let Some(pause_timeout_config) = server_config.pause_timeout {
+            config.server.pause_timeout = pause_timeout_config;
+        }
+        if let Some(socket_config) = server_config.socket {
+            if !socket_config.as_os_str().is_empty() {
+                config.server.socket = Some(socket_config);
+            }
+        }
+        // An empty token is the same as no token.
+        if let Some(token_config) = server_config.token {
+            if !token_config.is_empty() {
+                config.server.token = Some(token_config);
+            }
+        }
+    }
+
+    // dbgp config
+    if let Some(dbgp_config) = toml_config.dbgp {
+        if let Some(enabled_config) = dbgp_config.enabled {
+            config.dbgp.enabled = enabled_config;
+        }
+        if let Some(bind_config) = dbgp_config.bind {
+            config.dbgp.bind = bind_config;
+        }
+        if let Some(port_config) = dbgp_config.port {
+            config.dbgp.port = port_config;
+        }
+        if let Some(break_at_first_line_config) = dbgp_config.break_at_first_line {
+            config.dbgp.break_at_first_line = break_at_first_line_config;
         }
     }
 
     // keymap config
-    if toml_config.keymap.is_some() {
-        let keymap_config = toml_config.keymap.unwrap();
-        if keymap_config.leader_key.is_some() {
-            let leader_key_config = keymap_config.leader_key.unwrap();
+    if let Some(keymap_config) = toml_config.keymap {
+        if let Some(leader_key_config) = keymap_config.leader_key {
             config.keymap.leader_key = leader_key_config;
         }
     }
+
+    // editor config
+    if let Some(editor_config) = toml_config.editor {
+        // An empty command is the same as no command.
+        if let Some(command_config) = editor_config.command {
+            if !command_config.trim().is_empty() {
+                config.editor.command = Some(command_config);
+            }
+        }
+        if let Some(mode_config) = editor_config.mode {
+            config.editor.mode = mode_config;
+        }
+    }
+
+    // path mappings
+    if let Some(path_mappings_config) = toml_config.path_mappings {
+        config.path_mappings = path_mappings_config;
+    }
 }
 
-fn get_config_path() -> PathBuf {
+pub fn get_config_path() -> PathBuf {
     match std::env::consts::OS {
         "linux" => {
             home_dir().unwrap().join(".config/pmd/config.toml")
@@ -137,6 +440,67 @@ fn create_default_config_file(path: PathBuf) {
         let default_config_embed = Stub::get("config.toml").unwrap();
         let default_config = std::str::from_utf8(default_config_embed.data.as_ref()).unwrap();
 
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("Could not create the config directory");
+        }
+
         fs::write(path, default_config).expect("Could not write the default config");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(remote: &str, local: &str, connector_type: Option<&str>) -> PathMapping {
+        PathMapping {
+            remote: remote.to_string(),
+            local: local.to_string(),
+            connector_type: connector_type.map(String::from),
+        }
+    }
+
+    #[test]
+    fn maps_paths_under_the_remote_prefix() {
+        let mapping = mapping("/var/www", "/home/me/app", None);
+
+        assert_eq!(mapping.map("php", "/var/www/index.php"), Some("/home/me/app/index.php".to_string()));
+        assert_eq!(mapping.map("php", "/var/www"), Some("/home/me/app".to_string()));
+        assert_eq!(mapping.map("php", "/srv/www/index.php"), None);
+        assert_eq!(mapping.map("php", "var/www/index.php"), None);
+    }
+
+    #[test]
+    fn ignores_trailing_slashes() {
+        for (remote, local) in [("/var/www/", "/app/"), ("/var/www", "/app/"), ("/var/www/", "/app")] {
+            let mapping = mapping(remote, local, None);
+            assert_eq!(mapping.map("php", "/var/www/a.php"), Some("/app/a.php".to_string()));
+        }
+    }
+
+    #[test]
+    fn only_maps_whole_directories() {
+        let mapping = mapping("/var/www", "/app", None);
+
+        assert_eq!(mapping.map("php", "/var/www2/index.php"), None);
+        assert_eq!(mapping.map("php", "/var/www.php"), None);
+    }
+
+    #[test]
+    fn maps_only_the_configured_connector() {
+        let mapping = mapping("/var/www", "/app", Some("php"));
+
+        assert_eq!(mapping.map("php", "/var/www/a.php"), Some("/app/a.php".to_string()));
+        assert_eq!(mapping.map("dbgp", "/var/www/a.php"), None);
+    }
+
+    #[test]
+    fn maps_the_root() {
+        let root = mapping("/", "/mnt/container", None);
+        assert_eq!(root.map("rust", "/src/main.rs"), Some("/mnt/container/src/main.rs".to_string()));
+        assert_eq!(root.map("rust", "src/main.rs"), None);
+
+        let to_root = mapping("/app", "/", None);
+        assert_eq!(to_root.map("rust", "/app/a.rs"), Some("/a.rs".to_string()));
+    }
+}
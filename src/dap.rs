@@ -0,0 +1,482 @@
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{stdin, stdout, AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::{mpsc, watch},
+    time::timeout,
+};
+
+use crate::{
+    server::CommandKind,
+    store::{Resume, Store, Stored},
+};
+
+// How long `evaluate` waits for the adapter to reply.
+const EVALUATE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Largest message accepted from a client, the length comes from the peer.
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Exposes the breakpoints over the Debug Adapter Protocol.
+///
+/// Every breakpoint is presented as a stopped thread, its own location and
+/// its callstack as the stack frames and its payload as the variables.
+#[tokio::main(worker_threads = 1)]
+pub async fn run(store: Arc<Store>, changes: watch::Receiver<u64>, port: Option<u16>) {
+    // Keep the server accepting dumps while no client is connected.
+    let _changes = changes;
+
+    match port {
+        None => {
+            let mut adapter = Adapter::new(Arc::clone(&store), stdout());
+            let _ = adapter.serve(BufReader::new(stdin())).await;
+        }
+        Some(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))
+                .await
+                .expect("Could not bind the DAP port.");
+
+            // One client at a time, the next one can connect once it's gone.
+            while let Ok((stream, _)) = listener.accept().await {
+                let (reader, writer) = stream.into_split();
+                let mut adapter = Adapter::new(Arc::clone(&store), writer);
+                let _ = adapter.serve(BufReader::new(reader)).await;
+            }
+        }
+    }
+}
+
+struct Adapter<W> {
+    store: Arc<Store>,
+    writer: W,
+    seq: i64,
+    // Breakpoints the client already knows about.
+    announced: HashSet<u64>,
+    // Variable references, each one points into the payload of a breakpoint.
+    // Clients drop them once a thread stops or continues, so do we.
+    handles: Vec<(u64, String)>,
+    configured: bool,
+    // Whether the breakpoints from before the client connected were announced.
+    caught_up: bool,
+}
+
+impl<W: AsyncWrite + Unpin> Adapter<W> {
+    fn new(store: Arc<Store>, writer: W) -> Adapter<W> {
+        Adapter {
+            store,
+            writer,
+            seq: 0,
+            announced: HashSet::new(),
+            handles: vec![],
+            configured: false,
+            caught_up: false,
+        }
+    }
+
+    async fn serve<R>(&mut self, reader: R) -> io::Result<()>
+    where
+        R: AsyncBufRead + Unpin + Send + 'static,
+    {
+        // Reading happens in its own task, so waiting for new breakpoints
+        // never cancels a half read message.
+        let (tx, mut requests) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut reader = reader;
+            while let Ok(Some(message)) = read_message(&mut reader).await {
+                if tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut changes = self.store.subscribe();
+        loop {
+            tokio::select! {
+                request = requests.recv() => {
+                    let request = match request {
+                        Some(request) => request,
+                        None => return Ok(()),
+                    };
+                    if !self.handle(request).await? {
+                        return Ok(());
+                    }
+                }
+                changed = changes.changed(), if self.configured => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    self.announce().await?;
+                }
+            }
+        }
+    }
+
+    // Handles a request, returns false once the client disconnected.
+    async fn handle(&mut self, request: Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default().to_string();
+        let args = &request["arguments"];
+
+        let body = match command.as_str() {
+            "initialize" => {
+                self.respond(&request, Ok(json!({ "supportsConfigurationDoneRequest": true })))
+                    .await?;
+                self.event("initialized", json!({})).await?;
+                return Ok(true);
+            }
+            "launch" | "attach" | "setExceptionBreakpoints" | "pause" => Ok(json!({})),
+            // Breakpoints are set by the adapters in the code, not by the client.
+            "setBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => {
+                self.respond(&request, Ok(json!({}))).await?;
+                self.configured = true;
+                self.announce().await?;
+                return Ok(true);
+            }
+            "threads" => Ok(self.threads()),
+            "stackTrace" => self.stack_trace(args),
+            "scopes" => self.scopes(args),
+            "variables" => self.variables(args),
+            "continue" => self.resume(args, Resume::Continue),
+            "next" => self.resume(args, Resume::StepOver),
+            "stepIn" => self.resume(args, Resume::StepInto),
+            "stepOut" => self.resume(args, Resume::StepOut),
+            "evaluate" => self.evaluate(args).await,
+            "disconnect" => {
+                self.respond(&request, Ok(json!({}))).await?;
+                return Ok(false);
+            }
+            _ => Err(format!("Unsupported request: {}", command)),
+        };
+
+        self.respond(&request, body).await?;
+        Ok(true)
+    }
+
+    // Tells the client about breakpoints it hasn't seen yet.
+    async fn announce(&mut self) -> io::Result<()> {
+        let breakpoints = self.store.breakpoints();
+        let first_announce = !self.caught_up;
+        self.caught_up = true;
+
+        let new: Vec<Arc<Stored>> = breakpoints
+            .into_iter()
+            .filter(|breakpoint| !self.announced.contains(&breakpoint.id))
+            .collect();
+
+        // When connecting to a session with history, only stop on the latest
+        // breakpoint, the rest is listed in the threads.
+        let stop_on = if first_announce {
+            new.last().map(|breakpoint| breakpoint.id).into_iter().collect()
+        } else {
+            new.iter().map(|breakpoint| breakpoint.id).collect::<Vec<u64>>()
+        };
+
+        for breakpoint in &new {
+            self.announced.insert(breakpoint.id);
+            if !first_announce {
+                self.event("thread", json!({ "reason": "started", "threadId": breakpoint.id }))
+                    .await?;
+            }
+        }
+
+        if !stop_on.is_empty() {
+            self.handles.clear();
+        }
+
+        for id in stop_on {
+            self.event(
+                "stopped",
+                json!({
+                    "reason": "breakpoint",
+                    "threadId": id,
+                    "allThreadsStopped": false,
+                }),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    fn threads(&self) -> Value {
+        let threads: Vec<Value> = self
+            .store
+            .breakpoints()
+            .iter()
+            .map(|stored| {
+                let breakpoint = &stored.breakpoint;
+                json!({
+                    "id": stored.id,
+                    "name": format!("#{} [{}] {}", stored.id, breakpoint.line, breakpoint.filepath),
+                })
+            })
+            .collect();
+
+        json!({ "threads": threads })
+    }
+
+    // The breakpoint itself is the top frame, followed by its callstack.
+    fn stack_trace(&self, args: &Value) -> Result<Value, String> {
+        let stored = self.breakpoint(args["threadId"].as_u64())?;
+        let breakpoint = &stored.breakpoint;
+
+        let mut locations = vec![(breakpoint.filepath.clone(), breakpoint.line.clone())];
+        for frame in &breakpoint.callstack {
+            locations.push((frame.filepath.clone(), frame.line.clone()));
+        }
+
+        let frames: Vec<Value> = locations
+            .iter()
+            .enumerate()
+            .map(|(index, (filepath, line))| {
+                let name = filepath.rsplit(['/', '\\']).next().unwrap_or(filepath);
+                json!({
+                    "id": frame_id(stored.id, index),
+                    "name": format!("{}:{}", name, line),
+                    "source": { "name": name, "path": filepath },
+                    "line": line.parse::<u64>().unwrap_or(0),
+                    "column": 1,
+                })
+            })
+            .collect();
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    // The payload belongs to the top frame only.
+    fn scopes(&mut self, args: &Value) -> Result<Value, String> {
+        let (breakpoint_id, index) = split_frame_id(args["frameId"].as_u64().unwrap_or(0));
+        if index != 0 {
+            return Ok(json!({ "scopes": [] }));
+        }
+
+        let stored = self.breakpoint(Some(breakpoint_id))?;
+        let reference = self.handle_for(stored.id, String::new());
+
+        Ok(json!({
+            "scopes": [{
+                "name": "Payload",
+                "variablesReference": reference,
+                "expensive": false,
+            }]
+        }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        let reference = args["variablesReference"].as_u64().unwrap_or(0) as usize;
+        let (breakpoint_id, pointer) = self
+            .handles
+            .get(reference.wrapping_sub(1))
+            .cloned()
+            .ok_or_else(|| String::from("Unknown variables reference."))?;
+
+        let stored = self.breakpoint(Some(breakpoint_id))?;
+        let value = stored
+            .breakpoint
+            .payload
+            .pointer(&pointer)
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        let children: Vec<(String, String, Value)> = match &value {
+            Value::Object(obj) => obj
+                .iter()
+                .map(|(key, child)| (key.clone(), format!("{}/{}", pointer, escape(key)), child.clone()))
+                .collect(),
+            Value::Array(arr) => arr
+                .iter()
+                .enumerate()
+                .map(|(index, child)| (format!("[{}]", index), format!("{}/{}", pointer, index), child.clone()))
+                .collect(),
+            scalar => vec![(String::from("value"), pointer.clone(), scalar.clone())],
+        };
+
+        let variables: Vec<Value> = children
+            .into_iter()
+            .map(|(name, child_pointer, child)| {
+                let reference = match &child {
+                    Value::Object(_) | Value::Array(_) => self.handle_for(breakpoint_id, child_pointer),
+                    _ => 0,
+                };
+
+                json!({
+                    "name": name,
+                    "value": describe(&child),
+                    "type": type_name(&child),
+                    "variablesReference": reference,
+                })
+            })
+            .collect();
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn resume(&mut self, args: &Value, resume: Resume) -> Result<Value, String> {
+        let id = args["threadId"].as_u64().unwrap_or(0);
+        if let Some(stored) = self.store.breakpoint(id) {
+            let connector_type = &stored.breakpoint.connector_type;
+            if !resume.supported_by(connector_type) {
+                return Err(format!("{} breakpoints can't step, only continue.", connector_type));
+            }
+        }
+        if !self.store.resume(id, resume) {
+            return Err(format!("Breakpoint {} is not paused.", id));
+        }
+
+        self.handles.clear();
+        Ok(json!({ "allThreadsContinued": false }))
+    }
+
+    // Evaluation is delegated to the adapter holding a paused breakpoint.
+    async fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        let (breakpoint_id, _) = split_frame_id(args["frameId"].as_u64().unwrap_or(0));
+        let expression = args["expression"].as_str().unwrap_or_default().to_string();
+
+        let mut changes = self.store.subscribe();
+        let command_id = self
+            .store
+            .send_command(breakpoint_id, CommandKind::Eval { expression })
+            .ok_or_else(|| String::from("Only paused breakpoints can evaluate expressions."))?;
+
+        let wait_for_reply = async {
+            loop {
+                let reply = self
+                    .store
+                    .replies(breakpoint_id)
+                    .into_iter()
+                    .find(|reply| reply.command.id == command_id);
+                if let Some(reply) = reply {
+                    return reply;
+                }
+
+                if changes.changed().await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+
+        let reply = timeout(EVALUATE_TIMEOUT, wait_for_reply)
+            .await
+            .map_err(|_| String::from("The adapter didn't reply in time."))?;
+
+        match reply.error {
+            Some(error) => Err(error),
+            None => Ok(json!({
+                "result": describe(&reply.result),
+                "type": type_name(&reply.result),
+                "variablesReference": 0,
+            })),
+        }
+    }
+
+    fn breakpoint(&self, id: Option<u64>) -> Result<Arc<Stored>, String> {
+        let id = id.unwrap_or(0);
+        self.store
+            .breakpoint(id)
+            .ok_or_else(|| format!("Breakpoint {} doesn't exist.", id))
+    }
+
+    fn handle_for(&mut self, breakpoint_id: u64, pointer: String) -> usize {
+        self.handles.push((breakpoint_id, pointer));
+        self.handles.len()
+    }
+
+    async fn respond(&mut self, request: &Value, body: Result<Value, String>) -> io::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::String(message),
+        }
+
+        self.send(response).await
+    }
+
+    async fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+            .await
+    }
+
+    async fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        let content = message.to_string();
+        let packet = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+        self.writer.write_all(packet.as_bytes()).await?;
+        self.writer.flush().await
+    }
+}
+
+// Messages are framed with a `Content-Length` header.
+async fn read_message<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if (&mut *reader).take(1024).read_line(&mut header).await? == 0 {
+            return Ok(None);
+        }
+        if !header.ends_with('\n') {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid header."));
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length."))?;
+    if length > MAX_MESSAGE_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The message is too large."));
+    }
+    let mut content = vec![0; length];
+    reader.read_exact(&mut content).await?;
+
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// Frame ids hold the breakpoint id and the index of the frame.
+fn frame_id(breakpoint_id: u64, index: usize) -> u64 {
+    (breakpoint_id << 16) | index as u64
+}
+
+fn split_frame_id(frame_id: u64) -> (u64, usize) {
+    (frame_id >> 16, (frame_id & 0xffff) as usize)
+}
+
+// Escapes a key for a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(obj) => format!("object ({})", obj.len()),
+        Value::Array(arr) => format!("array ({})", arr.len()),
+        scalar => scalar.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "bool",
+        Value::Null => "null",
+    }
+}
@@ -0,0 +1,744 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::{Map, Number, Value};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{tcp::OwnedWriteHalf, TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+use crate::{
+    config::DbgpConfig,
+    server::{Breakpoint, Callstack, CommandKind},
+    store::{NextCommand, Resume, Store},
+};
+
+// How many truncated properties are fetched with `property_get` per break.
+const PROPERTY_GET_BUDGET: usize = 50;
+
+// Largest packet accepted from an engine, the length comes from the peer.
+const MAX_PACKET_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Listens for debugger engines speaking DBGp, e.g. Xdebug.
+///
+/// Every time the engine breaks, the stack and the local variables are
+/// turned into a paused breakpoint, resuming it from the UI sends the
+/// matching continuation command (run, step_into, ...) to the engine.
+#[tokio::main(worker_threads = 1)]
+pub async fn run(store: Arc<Store>, config: DbgpConfig) {
+    let addr = SocketAddr::new(config.bind, config.port);
+    let listener = TcpListener::bind(addr)
+        .await
+        .expect("Could not bind the DBGp port.");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(_) => continue,
+        };
+
+        let store = Arc::clone(&store);
+        let break_at_first_line = config.break_at_first_line;
+        tokio::spawn(async move {
+            let mut connection = Connection::new(stream, store);
+            // The engine closing the connection is how a debug session ends.
+            let _ = connection.debug(break_at_first_line).await;
+            connection.release();
+        });
+    }
+}
+
+/// One debug session with an engine.
+struct Connection {
+    engine: Engine,
+    store: Arc<Store>,
+    // The breakpoint that is currently paused, if any.
+    paused: Option<u64>,
+}
+
+/// Sends commands to an engine and reads its responses.
+struct Engine {
+    writer: OwnedWriteHalf,
+    // Packets are read by a task of their own, a read cancelled halfway,
+    // e.g. by resuming, would leave the stream misframed.
+    packets: mpsc::Receiver<io::Result<String>>,
+    transaction_id: u64,
+}
+
+/// A variable as reported by the engine.
+#[derive(Debug, Default)]
+struct Property {
+    name: String,
+    fullname: String,
+    kind: String,
+    value: String,
+    numchildren: usize,
+    children: Vec<Property>,
+}
+
+impl Connection {
+    fn new(stream: TcpStream, store: Arc<Store>) -> Connection {
+        Connection {
+            engine: Engine::new(stream),
+            store,
+            paused: None,
+        }
+    }
+
+    async fn debug(&mut self, break_at_first_line: bool) -> io::Result<()> {
+        // The engine introduces itself first.
+        self.engine.next_packet().await?;
+
+        for (name, value) in [("max_depth", "3"), ("max_children", "100"), ("max_data", "4096")] {
+            self.engine.send(&format!("feature_set -n {} -v {}", name, value)).await?;
+        }
+
+        let mut continuation = if break_at_first_line { "step_into" } else { "run" };
+        loop {
+            let response = self.engine.send(continuation).await?;
+            if !is_break(&response) {
+                // The script finished, let the engine go.
+                let _ = self.engine.send("stop").await;
+                return Ok(());
+            }
+
+            let breakpoint = self.engine.breakpoint().await?;
+            let id = self.store.push(breakpoint, None);
+            let resumed = self.store.pause(id);
+            self.paused = Some(id);
+
+            let resume = tokio::select! {
+                resume = resumed => resume.unwrap_or(Resume::Continue),
+                result = self.answer_commands(id) => {
+                    result?;
+                    Resume::Continue
+                }
+            };
+            self.paused = None;
+
+            continuation = match resume {
+                Resume::Continue => "run",
+                Resume::StepInto => "step_into",
+                Resume::StepOver => "step_over",
+                Resume::StepOut => "step_out",
+                Resume::Abort => {
+                    let _ = self.engine.send("stop").await;
+                    return Ok(());
+                }
+            };
+        }
+    }
+
+    // Answers the commands typed in the UI while the breakpoint is paused.
+    // Only returns on a connection error, the pause itself ends the wait.
+    async fn answer_commands(&mut self, id: u64) -> io::Result<()> {
+        loop {
+            let command = match self.store.next_command(id, Duration::from_secs(60)).await {
+                NextCommand::Command(command) => command,
+                NextCommand::Empty => continue,
+                // Wait for the resume to be picked up by the caller.
+                NextCommand::Resumed => std::future::pending().await,
+            };
+
+            let expression = match &command.kind {
+                CommandKind::Eval { expression } => expression.clone(),
+                CommandKind::Set { variable, value } => format!("{} = {}", variable, value),
+            };
+
+            let response = self
+                .engine
+                .send(&format!("eval -- {}", BASE64.encode(expression)))
+                .await?;
+            let (result, error) = match parse_response(&response) {
+                Ok((_, Some(error))) => (Value::Null, Some(error)),
+                Ok((properties, None)) => {
+                    let result = properties.into_iter().next().map(|p| p.to_value());
+                    (result.unwrap_or(Value::Null), None)
+                }
+                Err(error) => (Value::Null, Some(error)),
+            };
+
+            self.store.reply(id, command.id, result, error);
+        }
+    }
+
+    // Unpauses the breakpoint when the engine went away.
+    fn release(&mut self) {
+        if let Some(id) = self.paused.take() {
+            self.store.unpause(id);
+        }
+    }
+}
+
+impl Engine {
+    fn new(stream: TcpStream) -> Engine {
+        let (reader, writer) = stream.into_split();
+        let (tx, packets) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            loop {
+                let packet = read_packet(&mut reader).await;
+                let failed = packet.is_err();
+                if tx.send(packet).await.is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        Engine {
+            writer,
+            packets,
+            transaction_id: 0,
+        }
+    }
+
+    async fn next_packet(&mut self) -> io::Result<String> {
+        self.packets
+            .recv()
+            .await
+            .unwrap_or_else(|| Err(io::ErrorKind::UnexpectedEof.into()))
+    }
+
+    // Builds a breakpoint from the current stack and local variables.
+    async fn breakpoint(&mut self) -> io::Result<Breakpoint> {
+        let stack = self.send("stack_get").await?;
+        let frames = parse_stack(&stack);
+
+        let context = self.send("context_get -d 0 -c 0").await?;
+        let mut properties = parse_response(&context).map(|(p, _)| p).unwrap_or_default();
+
+        let mut budget = PROPERTY_GET_BUDGET;
+        for property in properties.iter_mut() {
+            self.expand(property, &mut budget).await?;
+        }
+
+        let mut payload = Map::new();
+        for property in properties {
+            payload.insert(property.name.clone(), property.to_value());
+        }
+
+        let (filepath, line) = frames.first().cloned().unwrap_or_default();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_secs())
+            .unwrap_or(0);
+
+        Ok(Breakpoint {
+            filepath,
+            line,
+            connector_type: String::from("dbgp"),
+            payload: Value::Object(payload),
+            timestamp: timestamp.to_string(),
+            callstack: frames
+                .into_iter()
+                .skip(1)
+                .map(|(filepath, line)| Callstack { filepath, line })
+                .collect(),
+        })
+    }
+
+    // Fetches the children the engine left out because of `max_depth`.
+    async fn expand(&mut self, property: &mut Property, budget: &mut usize) -> io::Result<()> {
+        if property.numchildren > 0 && property.children.is_empty() && *budget > 0 {
+            *budget -= 1;
+
+            let response = self
+                .send(&format!("property_get -d 0 -c 0 -n {}", quote(&property.fullname)))
+                .await?;
+            if let Ok((fetched, None)) = parse_response(&response) {
+                if let Some(fetched) = fetched.into_iter().next() {
+                    property.children = fetched.children;
+                }
+            }
+        }
+
+        for child in property.children.iter_mut() {
+            Box::pin(self.expand(child, budget)).await?;
+        }
+
+        Ok(())
+    }
+
+    // Sends a command and waits for its response.
+    async fn send(&mut self, command: &str) -> io::Result<String> {
+        self.transaction_id += 1;
+        let transaction_id = self.transaction_id.to_string();
+
+        // Arguments go between the command name and the `--` data.
+        let packet = match command.split_once(' ') {
+            Some((name, args)) => format!("{} -i {} {}\0", name, transaction_id, args),
+            None => format!("{} -i {}\0", command, transaction_id),
+        };
+        self.writer.write_all(packet.as_bytes()).await?;
+
+        loop {
+            let packet = self.next_packet().await?;
+            let document = match roxmltree::Document::parse(&packet) {
+                Ok(document) => document,
+                Err(_) => continue,
+            };
+
+            // Skip stream and notify packets.
+            let root = document.root_element();
+            if root.tag_name().name() == "response"
+                && root.attribute("transaction_id") == Some(transaction_id.as_str())
+            {
+                return Ok(packet);
+            }
+        }
+    }
+
+}
+
+// Packets are framed as `<length>\0<xml>\0`.
+async fn read_packet<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid DBGp packet length.");
+
+    // A length never takes more than a few digits.
+    let mut length = vec![];
+    if reader.take(32).read_until(0, &mut length).await? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if length.pop() != Some(0) {
+        return Err(invalid());
+    }
+
+    let length = String::from_utf8_lossy(&length)
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|length| *length <= MAX_PACKET_LENGTH)
+        .and_then(|length| length.checked_add(1))
+        .ok_or_else(invalid)?;
+
+    let mut data = vec![0; length];
+    reader.read_exact(&mut data).await?;
+    data.pop();
+
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+impl Property {
+    fn from_node(node: roxmltree::Node) -> Property {
+        let mut property = Property {
+            name: node.attribute("name").unwrap_or_default().to_string(),
+            fullname: node.attribute("fullname").unwrap_or_default().to_string(),
+            kind: node.attribute("type").unwrap_or_default().to_string(),
+            value: decode(node),
+            numchildren: node
+                .attribute("numchildren")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0),
+            children: vec![],
+        };
+
+        for child in node.children().filter(|c| c.is_element()) {
+            match child.tag_name().name() {
+                "property" => property.children.push(Property::from_node(child)),
+                // Sent instead of the attributes with extended properties.
+                "name" => property.name = decode(child),
+                "fullname" => property.fullname = decode(child),
+                "value" => property.value = decode(child),
+                _ => {}
+            }
+        }
+
+        property
+    }
+
+    fn to_value(&self) -> Value {
+        match self.kind.as_str() {
+            "array" | "hash" | "object" => {
+                // PHP lists are arrays with sequential numeric keys.
+                let is_list = self.kind == "array"
+                    && self
+                        .children
+                        .iter()
+                        .enumerate()
+                        .all(|(index, child)| child.name == index.to_string());
+
+                if is_list && !self.children.is_empty() {
+                    return Value::Array(self.children.iter().map(|c| c.to_value()).collect());
+                }
+
+                let mut map = Map::new();
+                for child in &self.children {
+                    map.insert(child.name.clone(), child.to_value());
+                }
+                Value::Object(map)
+            }
+            "int" => self
+                .value
+                .parse::<i64>()
+                .map(Value::from)
+                .unwrap_or_else(|_| Value::String(self.value.clone())),
+            "float" => self
+                .value
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number)
+                .unwrap_or_else(|| Value::String(self.value.clone())),
+            "bool" => Value::Bool(self.value == "1"),
+            "null" | "uninitialized" => Value::Null,
+            _ => Value::String(self.value.clone()),
+        }
+    }
+}
+
+fn is_break(response: &str) -> bool {
+    roxmltree::Document::parse(response)
+        .map(|document| document.root_element().attribute("status") == Some("break"))
+        .unwrap_or(false)
+}
+
+// Returns the top level properties of a response, or the engine's error.
+fn parse_response(response: &str) -> Result<(Vec<Property>, Option<String>), String> {
+    let document = roxmltree::Document::parse(response).map_err(|err| err.to_string())?;
+    let root = document.root_element();
+
+    let error = root
+        .children()
+        .find(|c| c.tag_name().name() == "error")
+        .map(|error| {
+            error
+                .children()
+                .find(|c| c.tag_name().name() == "message")
+                .and_then(|message| message.text())
+                .unwrap_or("Unknown error")
+                .to_string()
+        });
+
+    let properties = root
+        .children()
+        .filter(|c| c.tag_name().name() == "property")
+        .map(Property::from_node)
+        .collect();
+
+    Ok((properties, error))
+}
+
+// Returns the `(filepath, line)` of every stack frame, innermost first.
+fn parse_stack(response: &str) -> Vec<(String, String)> {
+    let document = match roxmltree::Document::parse(response) {
+        Ok(document) => document,
+        Err(_) => return vec![],
+    };
+
+    document
+        .root_element()
+        .children()
+        .filter(|c| c.tag_name().name() == "stack")
+        .map(|frame| {
+            let filename = frame.attribute("filename").unwrap_or_default();
+            let line = frame.attribute("lineno").unwrap_or_default();
+            (file_uri_to_path(filename), line.to_string())
+        })
+        .collect()
+}
+
+// Text content of a node, decoding it when it's base64 encoded.
+fn decode(node: roxmltree::Node) -> String {
+    let text: String = node
+        .children()
+        .filter(|c| c.is_text())
+        .filter_map(|c| c.text())
+        .collect();
+
+    if node.attribute("encoding") == Some("base64") {
+        return BASE64
+            .decode(text.trim())
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or(text);
+    }
+
+    text
+}
+
+fn file_uri_to_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    // Decode the percent encoded bytes, e.g. `%20` for a space.
+    let bytes = path.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Quotes a command argument, e.g. a property name like `$a["key"]`.
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::Session;
+
+    fn packet(xml: &str) -> String {
+        format!("{}\0{}\0", xml.len(), xml)
+    }
+
+    #[tokio::test]
+    async fn reads_consecutive_packets() {
+        let data = format!("{}{}", packet("<init/>"), packet("<response status=\"break\"/>"));
+        let mut reader = data.as_bytes();
+
+        assert_eq!(read_packet(&mut reader).await.unwrap(), "<init/>");
+        assert_eq!(read_packet(&mut reader).await.unwrap(), "<response status=\"break\"/>");
+        assert_eq!(
+            read_packet(&mut reader).await.unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_broken_packets() {
+        let mut reader = "abc\0<init/>\0".as_bytes();
+        assert_eq!(read_packet(&mut reader).await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // The body is shorter than the length says.
+        let mut reader = "100\0<init/>\0".as_bytes();
+        assert_eq!(read_packet(&mut reader).await.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        // Lengths past the cap are refused before anything is allocated.
+        for length in ["18446744073709551615", "99999999999999", "99999999999999999999999999999999999"] {
+            let data = format!("{}\0<init/>\0", length);
+            let mut reader = data.as_bytes();
+            assert_eq!(read_packet(&mut reader).await.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn converts_file_uris_to_paths() {
+        assert_eq!(file_uri_to_path("file:///var/www/index.php"), "/var/www/index.php");
+        assert_eq!(file_uri_to_path("file:///var/www/my%20app/a.php"), "/var/www/my app/a.php");
+        assert_eq!(file_uri_to_path("file:///%C3%A9.php"), "/é.php");
+        assert_eq!(file_uri_to_path("/already/a/path.php"), "/already/a/path.php");
+        // Broken escapes are kept as they are.
+        assert_eq!(file_uri_to_path("file:///a%zz%2"), "/a%zz%2");
+    }
+
+    #[test]
+    fn quotes_arguments() {
+        assert_eq!(quote("$user"), "\"$user\"");
+        assert_eq!(quote("$a[\"key\"]"), "\"$a[\\\"key\\\"]\"");
+        assert_eq!(quote("$path\\to"), "\"$path\\\\to\"");
+    }
+
+    // Answers every `property_get` with a property that is truncated again
+    // and returns how many were asked for.
+    async fn serve_endless_properties(listener: TcpListener) -> usize {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let mut fetched = 0;
+        loop {
+            let mut command = vec![];
+            if reader.read_until(0, &mut command).await.unwrap() == 0 {
+                return fetched;
+            }
+
+            let command = String::from_utf8_lossy(&command).into_owned();
+            let mut words = command.trim_end_matches('\0').split_whitespace();
+            assert_eq!(words.next(), Some("property_get"));
+            let transaction_id = words.skip_while(|word| *word != "-i").nth(1).unwrap().to_string();
+
+            fetched += 1;
+            let xml = format!(
+                "<response command=\"property_get\" transaction_id=\"{}\">\
+                 <property name=\"a\" fullname=\"$a\" type=\"array\" numchildren=\"1\">\
+                 <property name=\"0\" fullname=\"$a[0]\" type=\"array\" numchildren=\"1\"/>\
+                 </property></response>",
+                transaction_id
+            );
+            writer.write_all(packet(&xml).as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn stops_expanding_properties_at_the_budget() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(serve_endless_properties(listener));
+
+        let mut engine = Engine::new(TcpStream::connect(addr).await.unwrap());
+        let mut property = Property {
+            name: "a".to_string(),
+            fullname: "$a".to_string(),
+            kind: "array".to_string(),
+            numchildren: 1,
+            ..Property::default()
+        };
+        let mut budget = PROPERTY_GET_BUDGET;
+        engine.expand(&mut property, &mut budget).await.unwrap();
+        drop(engine);
+
+        assert_eq!(budget, 0);
+        assert_eq!(server.await.unwrap(), PROPERTY_GET_BUDGET);
+
+        let mut depth = 0;
+        let mut node = &property;
+        while let Some(child) = node.children.first() {
+            depth += 1;
+            node = child;
+        }
+        assert_eq!(depth, PROPERTY_GET_BUDGET);
+    }
+
+    // Plays an engine that answers the commands in the order given, `TID`
+    // in a response is replaced with the transaction id of the command.
+    async fn serve_script(listener: TcpListener, script: Vec<(&'static str, String)>) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let init = "<init appid=\"1\" idekey=\"pmd\" language=\"PHP\" fileuri=\"file:///var/www/index.php\"/>";
+        writer.write_all(packet(init).as_bytes()).await.unwrap();
+
+        for (expected, response) in script {
+            let mut command = vec![];
+            reader.read_until(0, &mut command).await.unwrap();
+
+            let command = String::from_utf8_lossy(&command).into_owned();
+            let mut words = command.trim_end_matches('\0').split_whitespace();
+            assert_eq!(words.next(), Some(expected));
+            let transaction_id = words.skip_while(|word| *word != "-i").nth(1).unwrap();
+
+            let response = response.replace("TID", transaction_id);
+            writer.write_all(packet(&response).as_bytes()).await.unwrap();
+        }
+    }
+
+    fn response(command: &'static str, attributes: &str, children: &str) -> (&'static str, String) {
+        let xml = format!(
+            "<response command=\"{}\" transaction_id=\"TID\" {}>{}</response>",
+            command, attributes, children
+        );
+        (command, xml)
+    }
+
+    async fn wait_for_pause(store: &Store, id: u64) {
+        let paused = async {
+            while !store.paused().contains(&id) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), paused)
+            .await
+            .expect("The breakpoint was never paused.");
+    }
+
+    #[tokio::test]
+    async fn turns_breaks_into_paused_breakpoints() {
+        let context = format!(
+            "<property name=\"$list\" fullname=\"$list\" type=\"array\" numchildren=\"2\">\
+             <property name=\"0\" fullname=\"$list[0]\" type=\"int\"><![CDATA[1]]></property>\
+             <property name=\"1\" fullname=\"$list[1]\" type=\"float\"><![CDATA[2.5]]></property>\
+             </property>\
+             <property name=\"$user\" fullname=\"$user\" type=\"array\" numchildren=\"2\">\
+             <property name=\"name\" fullname=\"$user['name']\" type=\"string\" encoding=\"base64\"><![CDATA[{}]]></property>\
+             <property name=\"admin\" fullname=\"$user['admin']\" type=\"bool\"><![CDATA[1]]></property>\
+             </property>\
+             <property name=\"$point\" fullname=\"$point\" type=\"object\" classname=\"Point\" numchildren=\"1\">\
+             <property name=\"x\" fullname=\"$point->x\" type=\"int\"><![CDATA[3]]></property>\
+             </property>\
+             <property name=\"$options\" fullname=\"$options\" type=\"hash\" numchildren=\"1\">\
+             <property name=\"0\" fullname=\"$options[0]\" type=\"null\"></property>\
+             </property>\
+             <property name=\"$empty\" fullname=\"$empty\" type=\"array\" numchildren=\"0\"></property>\
+             <property name=\"$later\" fullname=\"$later\" type=\"uninitialized\"></property>",
+            BASE64.encode("Ada <Lovelace>")
+        );
+        let script = vec![
+            response("feature_set", "success=\"1\"", ""),
+            response("feature_set", "success=\"1\"", ""),
+            response("feature_set", "success=\"1\"", ""),
+            response("run", "status=\"break\" reason=\"ok\"", ""),
+            response(
+                "stack_get",
+                "",
+                "<stack where=\"greet\" level=\"0\" type=\"file\" filename=\"file:///var/www/my%20app/index.php\" lineno=\"12\"/>\
+                 <stack where=\"{main}\" level=\"1\" type=\"file\" filename=\"file:///var/www/my%20app/bootstrap.php\" lineno=\"3\"/>",
+            ),
+            response("context_get", "context=\"0\"", &context),
+            response("step_into", "status=\"break\" reason=\"ok\"", ""),
+            response(
+                "stack_get",
+                "",
+                "<stack where=\"greet\" level=\"0\" type=\"file\" filename=\"file:///var/www/my%20app/index.php\" lineno=\"13\"/>",
+            ),
+            response("context_get", "context=\"0\"", ""),
+            response("stop", "status=\"stopped\" reason=\"ok\"", ""),
+        ];
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = tokio::spawn(serve_script(listener, script));
+
+        let store = Arc::new(Store::new(Session::temporary("dbgp-break"), 100, vec![]));
+        let mut connection = Connection::new(TcpStream::connect(addr).await.unwrap(), Arc::clone(&store));
+        let debug = tokio::spawn(async move { connection.debug(false).await });
+
+        wait_for_pause(&store, 1).await;
+        let first = store.breakpoint(1).unwrap();
+        assert_eq!(first.breakpoint.filepath, "/var/www/my app/index.php");
+        assert_eq!(first.breakpoint.line, "12");
+        assert_eq!(first.breakpoint.connector_type, "dbgp");
+        assert_eq!(
+            first.breakpoint.callstack,
+            vec![Callstack {
+                filepath: "/var/www/my app/bootstrap.php".to_string(),
+                line: "3".to_string(),
+            }]
+        );
+        assert_eq!(
+            first.breakpoint.payload,
+            json!({
+                "$list": [1, 2.5],
+                "$user": { "name": "Ada <Lovelace>", "admin": true },
+                "$point": { "x": 3 },
+                "$options": { "0": null },
+                "$empty": {},
+                "$later": null,
+            })
+        );
+
+        // Stepping breaks again on the next line.
+        assert!(store.resume(1, Resume::StepInto));
+        wait_for_pause(&store, 2).await;
+        let second = store.breakpoint(2).unwrap();
+        assert_eq!(second.breakpoint.line, "13");
+        assert!(second.breakpoint.callstack.is_empty());
+        assert_eq!(second.breakpoint.payload, json!({}));
+
+        // Aborting stops the script, the engine checks every command was sent.
+        assert!(store.resume(2, Resume::Abort));
+        debug.await.unwrap().unwrap();
+        engine.await.unwrap();
+        assert!(store.paused().is_empty());
+    }
+}
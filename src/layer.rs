@@ -0,0 +1,152 @@
+//! Forwards `tracing` events to pmd.
+//!
+//! ```no_run
+//! use tracing_subscriber::prelude::*;
+//!
+//! tracing_subscriber::registry().with(pmd::layer::Layer::new()).init();
+//! ```
+
+use serde_json::{json, Map, Value};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+use crate::{
+    client::{should_forward, Background, Client},
+    protocol::{Breakpoint, Callstack},
+};
+
+/// A `tracing_subscriber` layer that sends every event as a breakpoint.
+///
+/// The event's fields, level and target become the payload and its spans,
+/// innermost first, become the callstack.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    sender: Background,
+}
+
+impl Layer {
+    /// Sends to the server configured in the environment, see `Client::from_env`.
+    pub fn new() -> Layer {
+        Layer::with_client(Client::from_env())
+    }
+
+    pub fn with_client(client: Client) -> Layer {
+        Layer {
+            sender: Background::new(client),
+        }
+    }
+}
+
+impl Default for Layer {
+    fn default() -> Layer {
+        Layer::new()
+    }
+}
+
+// Fields of a span, kept in its extensions until an event needs them.
+struct SpanFields(Map<String, Value>);
+
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for Layer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(fields));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !should_forward(metadata.target()) {
+            return;
+        }
+
+        let mut fields = Map::new();
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let mut spans = vec![];
+        let mut callstack = vec![];
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                let span_fields = span
+                    .extensions()
+                    .get::<SpanFields>()
+                    .map(|SpanFields(fields)| fields.clone())
+                    .unwrap_or_default();
+                spans.push(json!({ "name": span.name(), "fields": span_fields }));
+
+                let span_metadata = span.metadata();
+                callstack.push(Callstack {
+                    filepath: span_metadata.file().unwrap_or(span_metadata.target()).to_string(),
+                    line: span_metadata.line().unwrap_or(0).to_string(),
+                });
+            }
+        }
+
+        let payload = json!({
+            "level": metadata.level().to_string(),
+            "target": metadata.target(),
+            "fields": fields,
+            "spans": spans,
+        });
+
+        let mut breakpoint = Breakpoint::new(
+            metadata.file().unwrap_or(metadata.target()),
+            metadata.line().unwrap_or(0).to_string(),
+            "tracing",
+            payload,
+        );
+        breakpoint.callstack = callstack;
+
+        self.sender.send(breakpoint);
+    }
+}
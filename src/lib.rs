@@ -0,0 +1,32 @@
+//! Protocol types and a client for sending breakpoints to pmd.
+//!
+//! ```no_run
+//! let user = serde_json::json!({ "id": 1, "roles": ["admin"] });
+//! pmd::dump!(user);
+//! ```
+
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "tracing")]
+pub mod layer;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod protocol;
+
+pub use protocol::{Breakpoint, Callstack};
+
+/// Sends the values to pmd, keyed by their expressions, together with the
+/// file, line and callstack of the call.
+///
+/// Errors are printed to stderr, a dump never fails the program.
+#[cfg(feature = "client")]
+#[macro_export]
+macro_rules! dump {
+    ($($value:expr),+ $(,)?) => {
+        $crate::client::dump(
+            file!(),
+            line!(),
+            vec![$((stringify!($value), $crate::client::to_payload(&$value))),+],
+        )
+    };
+}
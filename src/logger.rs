@@ -0,0 +1,82 @@
+//! Forwards `log` records to pmd.
+//!
+//! ```no_run
+//! pmd::logger::Logger::new().init().unwrap();
+//! ```
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::json;
+
+use crate::{
+    client::{should_forward, Background, Client},
+    protocol::Breakpoint,
+};
+
+/// A logger that sends every record as a breakpoint, with its level, target
+/// and message as the payload.
+#[derive(Debug)]
+pub struct Logger {
+    sender: Background,
+    level: LevelFilter,
+}
+
+impl Logger {
+    /// Sends to the server configured in the environment, see `Client::from_env`.
+    pub fn new() -> Logger {
+        Logger::with_client(Client::from_env())
+    }
+
+    pub fn with_client(client: Client) -> Logger {
+        Logger {
+            sender: Background::new(client),
+            level: LevelFilter::Trace,
+        }
+    }
+
+    /// Only forwards records up to this level, everything by default.
+    pub fn level(mut self, level: LevelFilter) -> Logger {
+        self.level = level;
+        self
+    }
+
+    /// Sets this as the global logger.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Logger {
+        Logger::new()
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && should_forward(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let payload = json!({
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        });
+
+        let breakpoint = Breakpoint::new(
+            record.file().unwrap_or(record.target()),
+            record.line().unwrap_or(0).to_string(),
+            "log",
+            payload,
+        );
+
+        self.sender.send(breakpoint);
+    }
+
+    fn flush(&self) {}
+}
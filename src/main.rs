@@ -1,20 +1,78 @@
-use std::thread;
+use std::{process, sync::Arc, thread};
 
-use tokio::sync::broadcast;
-
-use crate::{server::Breakpoint, config::Config};
+use crate::{config::{Config, Mode}, session::Session, store::Store};
 
 mod server;
 mod config;
+mod dap;
+mod dbgp;
+mod run;
+mod session;
+mod send;
+mod store;
+mod tail;
 mod ui;
 
 fn main() {
     let mut config = Config::new();
     config::parse(&mut config);
 
-    let config_port = config.port;
+    // Sending only talks to a running pmd, there is nothing to serve.
+    if let Mode::Send(options) = &config.mode {
+        send::run(&config.server, options);
+        return;
+    }
+
+    let server_config = config.server.clone();
+
+    // Bound up front, so a socket in use stops pmd before anything starts.
+    let mut socket = server::UnixSocket::bind(&server_config).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let listener = socket.as_mut().and_then(|socket| socket.take_listener());
+
+    let session = Session::open(config.session.clone());
+    let session_name = session.name.clone();
+    let store = Arc::new(Store::new(
+        session,
+        config.server.max_breakpoints,
+        config.path_mappings.clone(),
+    ));
+
+    // Subscribe before the server starts, so no dump is refused while
+    // the front end is still starting up.
+    let changes = store.subscribe();
+
+    let server_store = Arc::clone(&store);
+    let thread_server_config = server_config.clone();
+    thread::spawn(move || { server::run(server_store, thread_server_config, listener); });
+
+    if config.dbgp.enabled {
+        let dbgp_store = Arc::clone(&store);
+        let dbgp_config = config.dbgp.clone();
+        thread::spawn(move || { dbgp::run(dbgp_store, dbgp_config); });
+    }
+
+    let runner = config.run.as_ref().map(|command| {
+        let buffer = config.mode == Mode::Tui;
+        run::spawn(Arc::clone(&store), command, buffer).unwrap_or_else(|err| {
+            eprintln!("Could not run `{}`: {}", command.join(" "), err);
+            process::exit(1);
+        })
+    });
+
+    match config.mode {
+        Mode::Tui => ui::render(store, changes, config, session_name),
+        Mode::Dap { port } => dap::run(store, changes, port),
+        Mode::Tail { json } => tail::run(store, changes, json),
+        Mode::Send(_) => unreachable!("Send doesn't start the server."),
+    }
+
+    // `process::exit` doesn't run destructors, remove the socket first.
+    drop(socket);
 
-    let (tx, rx) = broadcast::channel::<Breakpoint>(100);
-    thread::spawn(move || { server::run(tx, config_port); });
-    ui::render(rx, config);
+    if let Some(runner) = runner {
+        process::exit(runner.finish());
+    }
 }
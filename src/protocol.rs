@@ -0,0 +1,42 @@
+//! The types adapters send to the pmd server.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A single dump, as sent to `POST /dump`.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, PartialEq)]
+pub struct Breakpoint {
+    pub filepath: String,
+    pub line: String,
+    pub connector_type: String,
+    pub payload: Value,
+    pub timestamp: String,
+    pub callstack: Vec<Callstack>,
+}
+
+impl Breakpoint {
+    /// Creates a breakpoint without a callstack, timestamped with the current
+    /// unix time.
+    pub fn new(filepath: impl Into<String>, line: impl Into<String>, connector_type: impl Into<String>, payload: Value) -> Breakpoint {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("System time is before the UNIX epoch.")
+            .as_secs();
+
+        Breakpoint {
+            filepath: filepath.into(),
+            line: line.into(),
+            connector_type: connector_type.into(),
+            payload,
+            timestamp: timestamp.to_string(),
+            callstack: vec![],
+        }
+    }
+}
+
+/// A frame of the code that led to the breakpoint.
+#[derive(Debug, Deserialize, Serialize, Clone, Eq, Hash, PartialEq)]
+pub struct Callstack {
+    pub filepath: String,
+    pub line: String,
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::VecDeque,
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::store::Store;
+
+use self::parser::{Parsed, Parser};
+
+mod parser;
+
+// How often the runner checks whether the child has exited.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+// Output held back while the TUI runs, the oldest is dropped past this.
+const MAX_BUFFERED_BYTES: usize = 8 * 1024 * 1024;
+
+/// Output of the child, kept in the order it was written.
+#[derive(Debug)]
+enum Output {
+    Stdout(Vec<u8>),
+    Stderr(String),
+}
+
+impl Output {
+    fn len(&self) -> usize {
+        match self {
+            Output::Stdout(bytes) => bytes.len(),
+            Output::Stderr(line) => line.len(),
+        }
+    }
+}
+
+/// Held back output, keeps the newest `MAX_BUFFERED_BYTES` of it.
+#[derive(Debug, Default)]
+struct OutputBuffer {
+    entries: VecDeque<Output>,
+    bytes: usize,
+    // Bytes dropped from the front to stay under the limit.
+    dropped: usize,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, output: Output) {
+        self.bytes += output.len();
+        self.entries.push_back(output);
+
+        while self.bytes > MAX_BUFFERED_BYTES {
+            match self.entries.pop_front() {
+                Some(oldest) => {
+                    self.bytes -= oldest.len();
+                    self.dropped += oldest.len();
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// A child process whose `dbg!` output and panics are turned into breakpoints.
+#[derive(Debug)]
+pub struct Runner {
+    child: Arc<Mutex<Child>>,
+    output: Arc<Mutex<OutputBuffer>>,
+    handle: JoinHandle<i32>,
+}
+
+/// Spawns the command. With `buffer` its output is held back until `finish`,
+/// so it doesn't draw over the TUI, otherwise it's passed through right away.
+pub fn spawn(store: Arc<Store>, command: &[String], buffer: bool) -> io::Result<Runner> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No command given."))?;
+
+    let mut process = Command::new(program);
+    process.args(args).stderr(Stdio::piped());
+    // Panics only carry a callstack with backtraces enabled.
+    if env::var_os("RUST_BACKTRACE").is_none() {
+        process.env("RUST_BACKTRACE", "1");
+    }
+    // The TUI owns the terminal, so the child can't read from it.
+    if buffer {
+        process.stdout(Stdio::piped()).stdin(Stdio::null());
+    }
+
+    let mut child = process.spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take().expect("The child's stderr is not piped.");
+
+    let child = Arc::new(Mutex::new(child));
+    let output = Arc::new(Mutex::new(OutputBuffer::default()));
+
+    let thread_child = Arc::clone(&child);
+    let thread_output = Arc::clone(&output);
+    let handle = thread::spawn(move || {
+        let stdout_reader = stdout.map(|mut stdout| {
+            let output = Arc::clone(&thread_output);
+            thread::spawn(move || {
+                let mut chunk = [0; 8192];
+                while let Ok(read) = stdout.read(&mut chunk) {
+                    if read == 0 {
+                        break;
+                    }
+                    output.lock().unwrap().push(Output::Stdout(chunk[..read].to_vec()));
+                }
+            })
+        });
+
+        let handle_parsed = |parsed: Vec<Parsed>| {
+            for parsed in parsed {
+                match parsed {
+                    Parsed::Breakpoint(breakpoint) => {
+                        store.push(breakpoint, None);
+                    }
+                    Parsed::Line(line) if buffer => {
+                        thread_output.lock().unwrap().push(Output::Stderr(line));
+                    }
+                    Parsed::Line(line) => eprintln!("{}", line),
+                }
+            }
+        };
+
+        let mut parser = Parser::default();
+        let mut reader = BufReader::new(stderr);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+
+            let text = String::from_utf8_lossy(&line);
+            handle_parsed(parser.feed(text.trim_end_matches(['\n', '\r'])));
+        }
+        handle_parsed(parser.finish());
+
+        if let Some(reader) = stdout_reader {
+            let _ = reader.join();
+        }
+
+        let code = wait(&thread_child);
+        store.set_exit_code(code);
+        code
+    });
+
+    Ok(Runner {
+        child,
+        output,
+        handle,
+    })
+}
+
+impl Runner {
+    /// Kills the child if it's still running, replays the held back output
+    /// and returns the exit code.
+    pub fn finish(self) -> i32 {
+        {
+            let mut child = self.child.lock().unwrap();
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+            }
+        }
+
+        let code = self.handle.join().unwrap_or(1);
+
+        let mut buffer = self.output.lock().unwrap();
+        if buffer.dropped > 0 {
+            eprintln!("pmd: dropped the first {} bytes of the command's output.", buffer.dropped);
+        }
+
+        let mut stdout = io::stdout().lock();
+        for output in buffer.entries.drain(..) {
+            match output {
+                Output::Stdout(bytes) => {
+                    let _ = stdout.write_all(&bytes);
+                }
+                Output::Stderr(line) => {
+                    let _ = stdout.flush();
+                    eprintln!("{}", line);
+                }
+            }
+        }
+        let _ = stdout.flush();
+
+        code
+    }
+}
+
+// Polls, so `finish` can take the lock to kill the child in between.
+fn wait(child: &Mutex<Child>) -> i32 {
+    loop {
+        match child.lock().unwrap().try_wait() {
+            Ok(Some(status)) => return status.code().unwrap_or(1),
+            Ok(None) => {}
+            Err(_) => return 1,
+        }
+
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_oldest_output_past_the_limit() {
+        let mut buffer = OutputBuffer::default();
+        buffer.push(Output::Stderr("first".to_string()));
+        buffer.push(Output::Stdout(vec![b'x'; MAX_BUFFERED_BYTES - 10]));
+        buffer.push(Output::Stderr("last line".to_string()));
+
+        assert_eq!(buffer.entries.len(), 2);
+        assert_eq!(buffer.dropped, 5);
+        assert_eq!(buffer.bytes, MAX_BUFFERED_BYTES - 1);
+
+        buffer.push(Output::Stderr("x".repeat(MAX_BUFFERED_BYTES + 1)));
+        assert!(buffer.entries.is_empty());
+        assert_eq!(buffer.bytes, 0);
+    }
+}
@@ -0,0 +1,818 @@
+use serde_json::{json, Map, Value};
+
+use crate::server::{Breakpoint, Callstack};
+
+// Longest `dbg!` value or panic message that is collected, in lines.
+const MAX_LINES: usize = 10000;
+
+/// A line of the child's stderr, either passed through or turned into a
+/// breakpoint together with the lines that followed it.
+#[derive(Debug)]
+pub enum Parsed {
+    Line(String),
+    Breakpoint(Breakpoint),
+}
+
+/// Picks `dbg!` output and panics out of the stderr of a Rust program.
+#[derive(Debug, Default)]
+pub struct Parser {
+    state: State,
+}
+
+#[derive(Debug, Default)]
+enum State {
+    #[default]
+    Idle,
+    // A pretty printed value spanning several lines.
+    Dbg {
+        filepath: String,
+        line: String,
+        expression: String,
+        value: String,
+        lines: usize,
+    },
+    Panic {
+        filepath: String,
+        line: String,
+        thread: String,
+        message: Vec<String>,
+        callstack: Vec<Callstack>,
+        in_backtrace: bool,
+    },
+}
+
+impl Parser {
+    pub fn feed(&mut self, text: &str) -> Vec<Parsed> {
+        match std::mem::take(&mut self.state) {
+            State::Idle => self.start(text),
+            State::Dbg {
+                filepath,
+                line,
+                expression,
+                mut value,
+                lines,
+            } => {
+                value.push('\n');
+                value.push_str(text);
+
+                if depth(&value) > 0 && lines < MAX_LINES {
+                    self.state = State::Dbg {
+                        filepath,
+                        line,
+                        expression,
+                        value,
+                        lines: lines + 1,
+                    };
+                    return vec![];
+                }
+
+                vec![Parsed::Breakpoint(dbg_breakpoint(filepath, line, &expression, &value))]
+            }
+            State::Panic {
+                filepath,
+                line,
+                thread,
+                mut message,
+                mut callstack,
+                in_backtrace,
+            } => {
+                let trimmed = text.trim_start();
+
+                // The default hook ends with one of these notes.
+                if trimmed.starts_with("note: ") {
+                    return vec![Parsed::Breakpoint(panic_breakpoint(
+                        filepath, line, &thread, &message, callstack,
+                    ))];
+                }
+
+                if !in_backtrace && trimmed == "stack backtrace:" {
+                    self.state = State::Panic {
+                        filepath,
+                        line,
+                        thread,
+                        message,
+                        callstack,
+                        in_backtrace: true,
+                    };
+                    return vec![];
+                }
+
+                if in_backtrace {
+                    if let Some(location) = trimmed.strip_prefix("at ") {
+                        if let Some(frame) = parse_frame(location) {
+                            callstack.push(frame);
+                        }
+                    } else if !is_frame_symbol(trimmed) {
+                        // Not a backtrace line, so the panic is over.
+                        let mut parsed = vec![Parsed::Breakpoint(panic_breakpoint(
+                            filepath, line, &thread, &message, callstack,
+                        ))];
+                        parsed.extend(self.start(text));
+                        return parsed;
+                    }
+                } else if message.len() < MAX_LINES {
+                    message.push(text.to_string());
+                }
+
+                self.state = State::Panic {
+                    filepath,
+                    line,
+                    thread,
+                    message,
+                    callstack,
+                    in_backtrace,
+                };
+                vec![]
+            }
+        }
+    }
+
+    /// Flushes whatever is still being collected, once stderr is closed.
+    pub fn finish(&mut self) -> Vec<Parsed> {
+        match std::mem::take(&mut self.state) {
+            State::Idle => vec![],
+            State::Dbg {
+                filepath,
+                line,
+                expression,
+                value,
+                ..
+            } => vec![Parsed::Breakpoint(dbg_breakpoint(filepath, line, &expression, &value))],
+            State::Panic {
+                filepath,
+                line,
+                thread,
+                message,
+                callstack,
+                ..
+            } => vec![Parsed::Breakpoint(panic_breakpoint(
+                filepath, line, &thread, &message, callstack,
+            ))],
+        }
+    }
+
+    fn start(&mut self, text: &str) -> Vec<Parsed> {
+        if let Some((filepath, line, rest)) = parse_dbg_header(text) {
+            let (expression, value) = match rest.split_once(" = ") {
+                Some((expression, value)) => (expression.to_string(), value.to_string()),
+                // `dbg!()` without arguments only prints the location.
+                None => (rest.to_string(), String::new()),
+            };
+
+            if depth(&value) > 0 {
+                self.state = State::Dbg {
+                    filepath,
+                    line,
+                    expression,
+                    value,
+                    lines: 1,
+                };
+                return vec![];
+            }
+
+            return vec![Parsed::Breakpoint(dbg_breakpoint(filepath, line, &expression, &value))];
+        }
+
+        if let Some((thread, rest)) = parse_panic_header(text) {
+            // Since Rust 1.73 the message follows on the next lines, before
+            // that it was quoted on the same line.
+            let (location, message) = match rest.strip_suffix(':') {
+                Some(location) => (location.to_string(), vec![]),
+                None => match rest.rsplit_once("', ") {
+                    Some((message, location)) => (
+                        location.to_string(),
+                        vec![message.trim_start_matches('\'').to_string()],
+                    ),
+                    None => (rest.to_string(), vec![]),
+                },
+            };
+
+            if let Some(frame) = parse_frame(&location) {
+                self.state = State::Panic {
+                    filepath: frame.filepath,
+                    line: frame.line,
+                    thread,
+                    message,
+                    callstack: vec![],
+                    in_backtrace: false,
+                };
+                return vec![];
+            }
+        }
+
+        vec![Parsed::Line(text.to_string())]
+    }
+}
+
+// `[src/main.rs:12:5] expression = value`, the column was added in Rust 1.58.
+fn parse_dbg_header(text: &str) -> Option<(String, String, String)> {
+    let rest = text.strip_prefix('[')?;
+    let (location, rest) = rest
+        .split_once("] ")
+        .or_else(|| rest.strip_suffix(']').map(|location| (location, "")))?;
+    let frame = parse_frame(location)?;
+
+    // Log lines often start with a bracketed timestamp.
+    if !frame.filepath.ends_with(".rs") {
+        return None;
+    }
+
+    Some((frame.filepath, frame.line, rest.to_string()))
+}
+
+// `thread 'main' panicked at ...`, newer versions add the thread id,
+// `thread 'main' (1234) panicked at ...`.
+fn parse_panic_header(text: &str) -> Option<(String, String)> {
+    let rest = text.strip_prefix("thread '")?;
+    let (thread, rest) = rest.split_once("' ")?;
+
+    let rest = match rest.strip_prefix('(') {
+        Some(rest) => rest.split_once(") ")?.1,
+        None => rest,
+    };
+    let rest = rest.strip_prefix("panicked at ")?;
+
+    Some((thread.to_string(), rest.to_string()))
+}
+
+// `src/main.rs:12:5` or `src/main.rs:12`.
+fn parse_frame(location: &str) -> Option<Callstack> {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let last = parts.next()?;
+    let before = parts.next()?;
+
+    let (filepath, line) = if before.chars().all(|c| c.is_ascii_digit()) && !before.is_empty() {
+        (parts.next()?, before)
+    } else {
+        (location.trim().rsplit_once(':')?.0, last)
+    };
+
+    if filepath.is_empty() || line.is_empty() || !line.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(Callstack {
+        filepath: filepath.trim_start_matches("./").to_string(),
+        line: line.to_string(),
+    })
+}
+
+// `  12: crate::module::function`
+fn is_frame_symbol(text: &str) -> bool {
+    match text.split_once(": ") {
+        Some((index, _)) => !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+// Frames of the standard library and dependencies are left out.
+fn is_external(frame: &Callstack) -> bool {
+    frame.filepath.starts_with("/rustc/")
+        || frame.filepath.contains("/.cargo/registry/")
+        || frame.filepath.contains("/rustlib/")
+}
+
+fn dbg_breakpoint(filepath: String, line: String, expression: &str, value: &str) -> Breakpoint {
+    let payload = if value.is_empty() {
+        Value::Null
+    } else {
+        let mut payload = Map::new();
+        payload.insert(expression.to_string(), parse_debug(value));
+        Value::Object(payload)
+    };
+
+    Breakpoint::new(filepath, line, "rust", payload)
+}
+
+fn panic_breakpoint(
+    filepath: String,
+    line: String,
+    thread: &str,
+    message: &[String],
+    callstack: Vec<Callstack>,
+) -> Breakpoint {
+    let payload = json!({
+        "panic": message.join("\n"),
+        "thread": thread,
+    });
+
+    let mut breakpoint = Breakpoint::new(filepath, line, "rust", payload);
+    breakpoint.callstack = callstack
+        .into_iter()
+        .filter(|frame| !is_external(frame))
+        .collect();
+
+    breakpoint
+}
+
+// How many brackets are still open, ignoring the ones in string and char literals.
+fn depth(text: &str) -> i32 {
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            // A char literal, e.g. '{' or '\'', a lone quote is left alone.
+            '\'' => {
+                let rest: Vec<char> = chars.clone().take(12).collect();
+                let skip = match rest.first() {
+                    Some('\\') => rest.iter().skip(2).position(|c| *c == '\'').map(|i| i + 3),
+                    Some(_) if rest.get(1) == Some(&'\'') => Some(2),
+                    _ => None,
+                };
+                for _ in 0..skip.unwrap_or(0) {
+                    chars.next();
+                }
+            }
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth
+}
+
+/// Turns `Debug` output into JSON where it can, e.g. structs and maps become
+/// objects and vectors become arrays. Anything else is kept as a string.
+pub fn parse_debug(text: &str) -> Value {
+    let mut reader = DebugReader {
+        chars: text.trim().chars().collect(),
+        position: 0,
+    };
+
+    match reader.value() {
+        Some(value) if reader.at_end() => value,
+        _ => Value::String(text.trim().to_string()),
+    }
+}
+
+struct DebugReader {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl DebugReader {
+    fn value(&mut self) -> Option<Value> {
+        self.skip_whitespace();
+
+        match self.peek()? {
+            '"' => self.string().map(Value::String),
+            '\'' => self.char_literal().map(Value::String),
+            '[' => {
+                self.position += 1;
+                self.list(']').map(Value::Array)
+            }
+            '(' => {
+                self.position += 1;
+                let values = self.list(')')?;
+                Some(if values.is_empty() { Value::Null } else { Value::Array(values) })
+            }
+            '{' => {
+                self.position += 1;
+                self.map()
+            }
+            c if c == '-' || c.is_ascii_digit() => self.number(),
+            c if c.is_alphabetic() || c == '_' => self.named(),
+            _ => None,
+        }
+    }
+
+    // `true`, `None`, `Some(1)`, `Point { x: 1 }`, `Variant(1, 2)` or `Unit`.
+    fn named(&mut self) -> Option<Value> {
+        let name = self.identifier();
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.position += 1;
+                self.fields()
+            }
+            Some('(') => {
+                self.position += 1;
+                let mut values = self.list(')')?;
+                if name == "Some" && values.len() == 1 {
+                    return values.pop();
+                }
+
+                let value = if values.len() == 1 {
+                    values.pop()?
+                } else {
+                    Value::Array(values)
+                };
+                let mut object = Map::new();
+                object.insert(name, value);
+                Some(Value::Object(object))
+            }
+            _ => Some(match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "None" => Value::Null,
+                _ => Value::String(name),
+            }),
+        }
+    }
+
+    // Struct fields, after the opening brace.
+    fn fields(&mut self) -> Option<Value> {
+        let mut object = Map::new();
+
+        loop {
+            self.skip_whitespace();
+            match self.peek()? {
+                '}' => {
+                    self.position += 1;
+                    return Some(Value::Object(object));
+                }
+                // Non exhaustive structs end with `..`.
+                '.' => {
+                    self.position += 2;
+                }
+                _ => {
+                    let key = self.identifier();
+                    if key.is_empty() {
+                        return None;
+                    }
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    let value = self.value()?;
+                    object.insert(key, value);
+                }
+            }
+            self.separator('}')?;
+        }
+    }
+
+    // A map `{"a": 1}` or a set `{1, 2}`, after the opening brace.
+    fn map(&mut self) -> Option<Value> {
+        let mut object = Map::new();
+        let mut set = vec![];
+
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == '}' {
+                self.position += 1;
+                break;
+            }
+
+            let key = self.value()?;
+            self.skip_whitespace();
+            if self.peek()? == ':' {
+                self.position += 1;
+                let value = self.value()?;
+                let key = match key {
+                    Value::String(key) => key,
+                    key => key.to_string(),
+                };
+                object.insert(key, value);
+            } else {
+                set.push(key);
+            }
+            self.separator('}')?;
+        }
+
+        if set.is_empty() {
+            Some(Value::Object(object))
+        } else {
+            Some(Value::Array(set))
+        }
+    }
+
+    // Comma separated values, after the opening bracket.
+    fn list(&mut self, close: char) -> Option<Vec<Value>> {
+        let mut values = vec![];
+
+        loop {
+            self.skip_whitespace();
+            if self.peek()? == close {
+                self.position += 1;
+                return Some(values);
+            }
+
+            values.push(self.value()?);
+            self.separator(close)?;
+        }
+    }
+
+    // Consumes a comma, or checks the list is about to close.
+    fn separator(&mut self, close: char) -> Option<()> {
+        self.skip_whitespace();
+        match self.peek()? {
+            ',' => {
+                self.position += 1;
+                Some(())
+            }
+            c if c == close => Some(()),
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            let c = self.next()?;
+            match c {
+                '"' => return Some(string),
+                '\\' => string.push(self.escape()?),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn char_literal(&mut self) -> Option<String> {
+        self.expect('\'')?;
+        let c = match self.next()? {
+            '\\' => self.escape()?,
+            c => c,
+        };
+        self.expect('\'')?;
+
+        Some(c.to_string())
+    }
+
+    fn escape(&mut self) -> Option<char> {
+        Some(match self.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            'u' => {
+                self.expect('{')?;
+                let mut hex = String::new();
+                loop {
+                    match self.next()? {
+                        '}' => break,
+                        c => hex.push(c),
+                    }
+                }
+                char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+            }
+            c => c,
+        })
+    }
+
+    fn number(&mut self) -> Option<Value> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '+' || c == '_' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        let number = text
+            .parse::<i64>()
+            .map(Value::from)
+            .or_else(|_| text.parse::<u64>().map(Value::from))
+            .or_else(|_| text.parse::<f64>().map(Value::from));
+
+        // Durations, e.g. `1.5s`, and other units are kept as strings.
+        Some(number.unwrap_or(Value::String(text)))
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            // Paths like `std::io::ErrorKind` and raw identifiers like `r#type`.
+            if c.is_alphanumeric() || c == '_' || c == '#' {
+                self.position += 1;
+            } else if c == ':' && self.chars.get(self.position + 1) == Some(&':') {
+                self.position += 2;
+            } else {
+                break;
+            }
+        }
+
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        (self.next()? == expected).then_some(())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.position == self.chars.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[&str]) -> Vec<Parsed> {
+        let mut parser = Parser::default();
+        let mut parsed = vec![];
+        for line in lines {
+            parsed.extend(parser.feed(line));
+        }
+        parsed.extend(parser.finish());
+        parsed
+    }
+
+    fn breakpoints(lines: &[&str]) -> Vec<Breakpoint> {
+        parse(lines)
+            .into_iter()
+            .filter_map(|parsed| match parsed {
+                Parsed::Breakpoint(breakpoint) => Some(breakpoint),
+                Parsed::Line(_) => None,
+            })
+            .collect()
+    }
+
+    fn frames(breakpoint: &Breakpoint) -> Vec<(&str, &str)> {
+        breakpoint
+            .callstack
+            .iter()
+            .map(|frame| (frame.filepath.as_str(), frame.line.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_single_line_dbg() {
+        let parsed = breakpoints(&["[src/main.rs:12:5] x * 2 = 10"]);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].filepath, "src/main.rs");
+        assert_eq!(parsed[0].line, "12");
+        assert_eq!(parsed[0].connector_type, "rust");
+        assert_eq!(parsed[0].payload, json!({"x * 2": 10}));
+    }
+
+    #[test]
+    fn parses_dbg_without_column_or_value() {
+        let parsed = breakpoints(&["[src/lib.rs:7] name = \"pmd\"", "[./src/lib.rs:9:5]"]);
+
+        assert_eq!(parsed[0].line, "7");
+        assert_eq!(parsed[0].payload, json!({"name": "pmd"}));
+        assert_eq!(parsed[1].filepath, "src/lib.rs");
+        assert_eq!(parsed[1].payload, Value::Null);
+    }
+
+    #[test]
+    fn parses_multi_line_dbg() {
+        let parsed = breakpoints(&[
+            "[src/main.rs:20:5] &user = User {",
+            "    name: \"bob }\",",
+            "    role: Some(",
+            "        Admin,",
+            "    ),",
+            "    tags: [",
+            "        'a',",
+            "    ],",
+            "    manager: None,",
+            "}",
+        ]);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(
+            parsed[0].payload,
+            json!({"&user": {"name": "bob }", "role": "Admin", "tags": ["a"], "manager": null}})
+        );
+    }
+
+    #[test]
+    fn parses_panic_with_backtrace() {
+        let parsed = breakpoints(&[
+            "thread 'main' panicked at src/main.rs:8:5:",
+            "index out of bounds",
+            "stack backtrace:",
+            "   0: rust_begin_unwind",
+            "             at /rustc/90b35a6/library/std/src/panicking.rs:645:5",
+            "   1: app::run",
+            "             at ./src/main.rs:8:5",
+            "   2: app::main",
+            "             at ./src/main.rs:3:5",
+            "note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.",
+        ]);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].filepath, "src/main.rs");
+        assert_eq!(parsed[0].line, "8");
+        assert_eq!(parsed[0].payload, json!({"panic": "index out of bounds", "thread": "main"}));
+        assert_eq!(frames(&parsed[0]), vec![("src/main.rs", "8"), ("src/main.rs", "3")]);
+    }
+
+    #[test]
+    fn parses_panic_headers_of_other_versions() {
+        let parsed = breakpoints(&[
+            "thread 'worker' (24398) panicked at src/jobs.rs:41:9:",
+            "first line",
+            "second line",
+            "note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+            "thread 'main' panicked at 'called `Option::unwrap()` on a `None` value', src/main.rs:5:9",
+            "note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace",
+        ]);
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].filepath, "src/jobs.rs");
+        assert_eq!(parsed[0].payload, json!({"panic": "first line\nsecond line", "thread": "worker"}));
+        assert_eq!(parsed[1].line, "5");
+        assert_eq!(
+            parsed[1].payload,
+            json!({"panic": "called `Option::unwrap()` on a `None` value", "thread": "main"})
+        );
+    }
+
+    #[test]
+    fn keeps_interleaved_lines_in_order() {
+        let parsed = parse(&[
+            "Compiling...",
+            "[src/main.rs:2:5] a = 1",
+            "[2024-01-01 12:00:00] server started",
+            "thread 'main' panicked at src/main.rs:4:5:",
+            "boom",
+            "stack backtrace:",
+            "   0: app::main",
+            "             at ./src/main.rs:4:5",
+            "after the panic",
+        ]);
+
+        let kinds: Vec<String> = parsed
+            .iter()
+            .map(|parsed| match parsed {
+                Parsed::Line(line) => format!("line {}", line),
+                Parsed::Breakpoint(breakpoint) => format!("breakpoint {}", breakpoint.line),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                "line Compiling...",
+                "breakpoint 2",
+                "line [2024-01-01 12:00:00] server started",
+                "breakpoint 4",
+                "line after the panic",
+            ]
+        );
+    }
+
+    #[test]
+    fn flushes_partial_input() {
+        let parsed = breakpoints(&["[src/main.rs:3:5] config = Config {", "    port: 80,"]);
+
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].payload, json!({"config": "Config {\n    port: 80,"}));
+
+        let parsed = breakpoints(&["thread 'main' panicked at src/main.rs:1:1:", "cut off"]);
+        assert_eq!(parsed[0].payload, json!({"panic": "cut off", "thread": "main"}));
+    }
+
+    #[test]
+    fn passes_malformed_headers_through() {
+        let lines = [
+            "[src/main.rs:abc] x = 1",
+            "[src/main.rs]",
+            "thread 'main' panicked at nowhere",
+            "thread 'main' did something else",
+        ];
+        let parsed = parse(&lines);
+
+        assert_eq!(parsed.len(), lines.len());
+        assert!(parsed.iter().all(|parsed| matches!(parsed, Parsed::Line(_))));
+    }
+
+    #[test]
+    fn parses_debug_values() {
+        assert_eq!(parse_debug("Some(3)"), json!(3));
+        assert_eq!(parse_debug("(1, \"a\")"), json!([1, "a"]));
+        assert_eq!(parse_debug("Err(NotFound)"), json!({"Err": "NotFound"}));
+        assert_eq!(parse_debug("{\"a\": 1, \"b\": 2}"), json!({"a": 1, "b": 2}));
+        assert_eq!(parse_debug("{1, 2}"), json!([1, 2]));
+        assert_eq!(parse_debug("1.5s"), json!("1.5s"));
+        assert_eq!(parse_debug("Point { x: -1, .. }"), json!({"x": -1}));
+        // Anything that doesn't parse is kept as it is.
+        assert_eq!(parse_debug("<opaque> 1"), json!("<opaque> 1"));
+    }
+}
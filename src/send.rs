@@ -0,0 +1,210 @@
+use std::{
+    env,
+    io::{self, Read},
+    net::IpAddr,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::Value;
+
+use crate::{
+    config::{SendOptions, ServerConfig},
+    server::{Breakpoint, Callstack},
+};
+
+/// Sends a single breakpoint to the pmd listening on the configured port,
+/// prints the response and exits with 1 when it was rejected.
+pub fn run(config: &ServerConfig, options: &SendOptions) {
+    let breakpoint = build_breakpoint(options).unwrap_or_else(|err| fail(&err));
+    let body = serde_json::to_string(&breakpoint).expect("Could not serialize the breakpoint.");
+
+    let route = if options.pause { "break" } else { "dump" };
+    let url = format!("http://{}/{}", address(config), route);
+
+    let mut request = ureq::post(&url).set("Content-Type", "application/json");
+    if let Some(token) = &config.token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+
+    match request.send_string(&body) {
+        Ok(response) => {
+            println!("{}", response.into_string().unwrap_or_default());
+        }
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            let reason = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|body| {
+                    let error = &body["error"];
+                    let reason = error["reason"].as_str()?;
+                    match error["path"].as_str() {
+                        Some(path) if !path.is_empty() => Some(format!("{}: {}", path, reason)),
+                        _ => Some(reason.to_string()),
+                    }
+                })
+                .unwrap_or(body);
+
+            fail(&format!("pmd rejected the breakpoint ({}): {}", status, reason));
+        }
+        Err(err) => fail(&format!("Could not reach pmd: {}", err)),
+    }
+}
+
+fn build_breakpoint(options: &SendOptions) -> Result<Breakpoint, String> {
+    let payload = match options.payload.as_deref() {
+        // Only read stdin when asked to, scripts often have it open or
+        // piped into a loop that calls pmd.
+        Some("-") => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .map_err(|err| format!("Could not read the payload from stdin: {}", err))?;
+            parse_payload(input.trim_end())
+        }
+        Some(payload) => parse_payload(payload),
+        None => Value::Null,
+    };
+
+    let mut callstack = if options.shell_stack {
+        shell_stack()?
+    } else {
+        vec![]
+    };
+    // Without a location the caller's frame is the breakpoint itself.
+    let location = match (&options.file, &options.line) {
+        (Some(file), Some(line)) => (file.clone(), line.clone()),
+        _ if !callstack.is_empty() => {
+            let top = callstack.remove(0);
+            (options.file.clone().unwrap_or(top.filepath), options.line.clone().unwrap_or(top.line))
+        }
+        _ => return Err(String::from("Pass --file and --line, the shell stack is empty.")),
+    };
+
+    for frame in &options.frames {
+        callstack.push(parse_frame(frame)?);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the UNIX epoch.")
+        .as_secs();
+
+    Ok(Breakpoint {
+        filepath: location.0,
+        line: location.1,
+        connector_type: options.connector_type.clone(),
+        payload,
+        timestamp: timestamp.to_string(),
+        callstack,
+    })
+}
+
+// Anything that isn't JSON is sent as a string, so plain text can be piped in.
+fn parse_payload(payload: &str) -> Value {
+    serde_json::from_str(payload).unwrap_or_else(|_| Value::String(payload.to_string()))
+}
+
+fn parse_frame(frame: &str) -> Result<Callstack, String> {
+    match frame.rsplit_once(':') {
+        Some((filepath, line)) if !filepath.is_empty() && !line.is_empty() => Ok(Callstack {
+            filepath: filepath.to_string(),
+            line: line.to_string(),
+        }),
+        _ => Err(format!("Invalid frame `{}`, expected FILE:LINE.", frame)),
+    }
+}
+
+// Reads the stack exported by the `pmd_dump` helper, innermost frame first.
+fn shell_stack() -> Result<Vec<Callstack>, String> {
+    let sources = env::var("PMD_BASH_SOURCE")
+        .map_err(|_| String::from("PMD_BASH_SOURCE is not set, --shell-stack needs the pmd_dump helper."))?;
+    let lines = env::var("PMD_BASH_LINENO").unwrap_or_default();
+
+    Ok(parse_shell_stack(&sources, &lines))
+}
+
+// Both are bash arrays joined with newlines, including the helper's own
+// frame. `BASH_LINENO[i]` is the line in `BASH_SOURCE[i + 1]` that called
+// the function of frame `i`, the outermost one has nothing to pair with.
+fn parse_shell_stack(sources: &str, lines: &str) -> Vec<Callstack> {
+    sources
+        .split('\n')
+        .skip(1)
+        .zip(lines.split('\n'))
+        // Frames typed at the prompt have no file.
+        .filter(|(source, line)| !source.is_empty() && !line.is_empty())
+        .map(|(source, line)| Callstack {
+            filepath: source.to_string(),
+            line: line.to_string(),
+        })
+        .collect()
+}
+
+// A server listening on all interfaces is reached through the loopback.
+fn address(config: &ServerConfig) -> String {
+    let host = match config.bind {
+        IpAddr::V4(ip) if ip.is_unspecified() => "127.0.0.1".to_string(),
+        IpAddr::V6(ip) if ip.is_unspecified() => "[::1]".to_string(),
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    };
+
+    format!("{}:{}", host, config.port)
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(callstack: &[Callstack]) -> Vec<(&str, &str)> {
+        callstack
+            .iter()
+            .map(|frame| (frame.filepath.as_str(), frame.line.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_shell_stack() {
+        // pmd_dump from lib.sh, called on line 6 of deploy.sh inside a
+        // function that was called on line 20.
+        let callstack = parse_shell_stack("lib.sh\ndeploy.sh\ndeploy.sh", "6\n20\n0");
+        assert_eq!(frames(&callstack), vec![("deploy.sh", "6"), ("deploy.sh", "20")]);
+
+        // Defined and called at the interactive prompt.
+        assert!(parse_shell_stack("\n", "1\n0").is_empty());
+        assert!(parse_shell_stack("", "").is_empty());
+    }
+
+    #[test]
+    fn leaves_stdin_alone_without_a_payload() {
+        let mut options = SendOptions {
+            file: Some("deploy.sh".to_string()),
+            line: Some("3".to_string()),
+            payload: None,
+            frames: vec![],
+            shell_stack: false,
+            connector_type: "shell".to_string(),
+            pause: false,
+        };
+        assert_eq!(build_breakpoint(&options).unwrap().payload, Value::Null);
+
+        options.payload = Some("{\"env\": \"prod\"}".to_string());
+        assert_eq!(build_breakpoint(&options).unwrap().payload["env"], "prod");
+    }
+
+    #[test]
+    fn parses_frames() {
+        let frame = parse_frame("/srv/my app/run.sh:12").unwrap();
+        assert_eq!(frames(&[frame]), vec![("/srv/my app/run.sh", "12")]);
+
+        assert!(parse_frame("run.sh").is_err());
+        assert!(parse_frame("run.sh:").is_err());
+        assert!(parse_frame(":3").is_err());
+    }
+}
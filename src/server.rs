@@ -1,59 +1,912 @@
-use std::{sync::{Arc, Mutex}, net::SocketAddr};
+use std::{fmt, fs, sync::Arc, net::SocketAddr, path::PathBuf, time::Duration};
 
-use axum::{Router, routing::post, extract::State, Json};
-use serde::Deserialize;
-use tokio::sync::broadcast::{Sender, self};
+use axum::{
+    Router,
+    routing::{get, post},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        rejection::QueryRejection,
+        BodyStream, Path, Query, State,
+    },
+    body::Bytes,
+    http::{header, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures_util::StreamExt;
+#[cfg(unix)]
+use hyper::server::accept;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Value, json, error::Category};
+use tokio::{sync::broadcast::error::RecvError, time::timeout};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+pub use pmd::protocol::{Breakpoint, Callstack};
+
+use crate::{
+    config::ServerConfig,
+    store::{NextCommand, Store, Stored},
+};
+
+// Page size of `GET /breakpoints` when no limit is given, and the largest allowed.
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+// Longest line accepted by `/dump/stream`, the same as the body limit of
+// the other dump routes.
+const MAX_STREAM_LINE: usize = 2 * 1024 * 1024;
+
+// How long an adapter waits for a command before polling again.
+const COMMAND_POLL_TIMEOUT: Duration = Duration::from_secs(25);
 
 // Our shared state
 #[derive(Debug)]
 pub struct AppState {
-    pub data: Mutex<Vec<Breakpoint>>,
-    // Channel used to send messages from server to client.
-    pub tx: broadcast::Sender<Breakpoint>,
+    // Breakpoints shared with the front ends.
+    pub store: Arc<Store>,
+    // Shared secret the adapters need to send, if any.
+    pub token: Option<String>,
+    // How long a paused breakpoint waits for the user to continue.
+    pub pause_timeout: Duration,
+}
+
+/// A dump request that was refused by the server.
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub status: u16,
+    // Where in the request body the problem is, e.g. `callstack[0].line`.
+    pub path: String,
+    pub reason: String,
+    // The start of the request body, to help identify the request.
+    pub body: String,
+}
+
+impl Rejection {
+    fn new(status: StatusCode, path: String, reason: String, body: &[u8]) -> Rejection {
+        let body: String = String::from_utf8_lossy(body).chars().take(200).collect();
+
+        Rejection {
+            status: status.as_u16(),
+            path,
+            reason,
+            body,
+        }
+    }
+}
+
+/// A command sent from the UI to the adapter holding a paused breakpoint.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Command {
+    pub id: u64,
+    #[serde(flatten)]
+    pub kind: CommandKind,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CommandKind {
+    // Evaluate an expression in the scope of the breakpoint.
+    Eval { expression: String },
+    // Assign a new value, written in the adapter's language, to a variable.
+    Set { variable: String, value: String },
+}
+
+impl fmt::Display for CommandKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandKind::Eval { expression } => write!(f, "eval {}", expression),
+            CommandKind::Set { variable, value } => write!(f, "set {} {}", variable, value),
+        }
+    }
+}
+
+/// The adapter's answer to a command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub command: Command,
+    pub result: Value,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyRequest {
+    command_id: u64,
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Eq, Hash, PartialEq)]
-pub struct Breakpoint {
-    pub filepath: String,
-    pub line: String,
-    pub connector_type: String,
-    pub payload: String,
-    pub timestamp: String,
-    pub callstack: Vec<Callstack>,
+// `"pause": true` sent next to the breakpoint fields of a dump, it's not
+// part of the breakpoint itself.
+#[derive(Debug, Deserialize)]
+struct PauseRequest {
+    #[serde(default)]
+    pause: bool,
 }
 
-#[derive(Debug, Deserialize, Clone, Eq, Hash, PartialEq)]
-pub struct Callstack {
-    pub filepath: String,
-    pub line: String,
+/// Listener of the unix domain socket, handed over to `run`.
+#[cfg(unix)]
+pub type SocketListener = std::os::unix::net::UnixListener;
+#[cfg(not(unix))]
+pub type SocketListener = std::convert::Infallible;
+
+/// The unix domain socket this process bound. The socket file is removed
+/// when this is dropped, so a pmd never removes the socket of another one.
+#[derive(Debug)]
+pub struct UnixSocket {
+    path: PathBuf,
+    listener: Option<SocketListener>,
+}
+
+impl UnixSocket {
+    /// Binds the configured socket, if any. Fails when another pmd is
+    /// already listening on it.
+    #[cfg(unix)]
+    pub fn bind(config: &ServerConfig) -> Result<Option<UnixSocket>, String> {
+        let path = match &config.socket {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+
+        remove_stale_socket(&path)?;
+        let listener = SocketListener::bind(&path)
+            .map_err(|err| format!("Could not bind the unix socket {}: {}", path.display(), err))?;
+        // Tokio only takes over non-blocking listeners.
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| format!("Could not set up the unix socket {}: {}", path.display(), err))?;
+
+        Ok(Some(UnixSocket {
+            path,
+            listener: Some(listener),
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(_config: &ServerConfig) -> Result<Option<UnixSocket>, String> {
+        Ok(None)
+    }
+
+    /// Takes the listener for the server, the socket file stays owned by this.
+    pub fn take_listener(&mut self) -> Option<SocketListener> {
+        self.listener.take()
+    }
 }
 
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
 
 #[tokio::main(worker_threads = 1)]
 #[allow(unused_must_use)]
-pub async fn run(tx: Sender<Breakpoint>, port: u16) {
+pub async fn run(store: Arc<Store>, config: ServerConfig, socket: Option<SocketListener>) {
     tokio::spawn(async move {
-        let app_state = Arc::new(AppState { data: Mutex::new(vec![]), tx });
-        let app = Router::new().route("/dump", post(dump)).with_state(app_state);
+        let app_state = Arc::new(AppState {
+            store,
+            token: config.token,
+            pause_timeout: Duration::from_secs(config.pause_timeout),
+        });
+        let app = Router::new()
+            .route("/dump", post(dump))
+            .route("/break", post(dump_pause))
+            .route("/commands/:id", get(next_command))
+            .route("/commands/:id/reply", post(command_reply))
+            .route("/dump/batch", post(dump_batch))
+            .route("/dump/stream", post(dump_stream))
+            .route("/ws", get(live_feed))
+            .route("/breakpoints", get(list_breakpoints).delete(clear_breakpoints))
+            .route("/breakpoints/:id", get(get_breakpoint))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), authorize))
+            .with_state(app_state);
+
+        #[cfg(not(unix))]
+        let _ = socket;
+        #[cfg(unix)]
+        if let Some(socket) = socket {
+            let unix_app = app.clone();
+            tokio::spawn(async move { serve_unix(unix_app, socket).await });
+        }
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], port));
+        let addr = SocketAddr::new(config.bind, config.port);
         axum::Server::bind(&addr)
             .serve(app.into_make_service())
             .await
             .unwrap();
     }).await;
+}
+
+// Serves the same routes on a unix domain socket.
+#[cfg(unix)]
+async fn serve_unix(app: Router, socket: SocketListener) {
+    let listener = UnixListener::from_std(socket).expect("Could not listen on the unix socket.");
+    let incoming = accept::poll_fn(move |cx| {
+        listener
+            .poll_accept(cx)
+            .map(|result| Some(result.map(|(stream, _)| stream)))
+    });
+
+    axum::Server::builder(incoming)
+        .serve(app.into_make_service())
+        .await
+        .unwrap();
+}
+
+// A socket file left behind by a crashed pmd would make the bind fail.
+#[cfg(unix)]
+fn remove_stale_socket(socket: &std::path::Path) -> Result<(), String> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = match fs::symlink_metadata(socket) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(format!("Could not read {}: {}", socket.display(), err)),
+    };
+
+    // Most likely a typo in the config, never remove anything else.
+    if !metadata.file_type().is_socket() {
+        return Err(format!(
+            "{} exists and is not a unix socket, pick another path for the socket.",
+            socket.display()
+        ));
+    }
+
+    if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+        return Err(format!(
+            "The unix socket {} is already in use, is another pmd running?",
+            socket.display()
+        ));
+    }
+
+    fs::remove_file(socket)
+        .map_err(|err| format!("Could not remove the stale unix socket {}: {}", socket.display(), err))
+}
+
+/// Query string `authorize` looks at, browsers can't set headers on a
+/// WebSocket handshake.
+#[derive(Debug, Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+// Rejects requests without the configured bearer token. The live feed also
+// takes it as a `token` query parameter.
+async fn authorize<B>(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<TokenQuery>, QueryRejection>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let token = match &state.token {
+        Some(token) => token,
+        None => return next.run(request).await,
+    };
+
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.trim().to_string());
+    // Only for the feed, query strings end up in access logs.
+    let query_token = match query {
+        Ok(Query(query)) if request.uri().path() == "/ws" => query.token,
+        _ => None,
+    };
+
+    let authorized = bearer
+        .or(query_token)
+        .map(|value| tokens_match(&value, token))
+        .unwrap_or(false);
+
+    if !authorized {
+        state.store.unauthorized();
+        let rejection = Rejection::new(
+            StatusCode::UNAUTHORIZED,
+            String::new(),
+            "Missing or invalid bearer token.".to_string(),
+            &[],
+        );
+        return error_response(&rejection);
+    }
+
+    next.run(request).await
+}
+
+// Compares the whole token, so the time taken doesn't leak how much matched.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    if given.len() != expected.len() {
+        return false;
+    }
+
+    given
+        .bytes()
+        .zip(expected.bytes())
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+async fn dump(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    dump_single(state, body, false).await
+}
+
+/// Same as `/dump`, but the breakpoint always pauses the adapter.
+async fn dump_pause(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    dump_single(state, body, true).await
+}
+
+async fn dump_single(state: Arc<AppState>, body: Bytes, pause: bool) -> Response {
+    let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+    let breakpoint: Breakpoint = match parse_body(deserializer, &body) {
+        Ok(breakpoint) => breakpoint,
+        Err(rejection) => return reject(&state, rejection),
+    };
+    let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+    let request: PauseRequest = match parse_body(deserializer, &body) {
+        Ok(request) => request,
+        Err(rejection) => return reject(&state, rejection),
+    };
+
+    if !state.store.has_subscribers() {
+        return error_response(&unavailable(&body));
+    }
+
+    let pause = request.pause || pause;
+    let id = accept(&state, breakpoint);
+
+    if !pause {
+        return (StatusCode::OK, Json(json!({ "id": id }))).into_response();
+    }
+
+    // Hold the response until the user continues from the UI. The guard
+    // unpauses the breakpoint if the adapter goes away in the meantime.
+    let resumed = state.store.pause(id);
+    let _guard = PauseGuard {
+        store: Arc::clone(&state.store),
+        id,
+    };
+
+    let resumed = match timeout(state.pause_timeout, resumed).await {
+        Ok(Ok(resume)) => resume.as_str(),
+        _ => "timeout",
+    };
+
+    (StatusCode::OK, Json(json!({ "id": id, "resumed": resumed }))).into_response()
+}
+
+/// Long-polls the next command the user sent to a paused breakpoint.
+/// Responds with 204 when nothing was sent in time, and with 410 once the
+/// breakpoint is no longer paused so the adapter can stop polling.
+async fn next_command(State(state): State<Arc<AppState>>, Path(id): Path<u64>) -> Response {
+    match state.store.next_command(id, COMMAND_POLL_TIMEOUT).await {
+        NextCommand::Command(command) => {
+            (StatusCode::OK, Json(json!({ "command": command }))).into_response()
+        }
+        NextCommand::Empty => StatusCode::NO_CONTENT.into_response(),
+        NextCommand::Resumed => {
+            let rejection = Rejection::new(
+                StatusCode::GONE,
+                String::new(),
+                format!("Breakpoint {} is not paused.", id),
+                &[],
+            );
+            error_response(&rejection)
+        }
+    }
+}
+
+/// Stores the adapter's reply to a command, it's shown in the Inspection tree.
+async fn command_reply(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+    body: Bytes,
+) -> Response {
+    let deserializer = &mut serde_json::Deserializer::from_slice(&body);
+    let request: ReplyRequest = match parse_body(deserializer, &body) {
+        Ok(request) => request,
+        Err(rejection) => return reject(&state, rejection),
+    };
+
+    if !state
+        .store
+        .reply(id, request.command_id, request.result, request.error)
+    {
+        let rejection = Rejection::new(
+            StatusCode::NOT_FOUND,
+            String::from("command_id"),
+            format!(
+                "Command {} was not sent to breakpoint {}.",
+                request.command_id, id
+            ),
+            &body,
+        );
+        return reject(&state, rejection);
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+struct PauseGuard {
+    store: Arc<Store>,
+    id: u64,
+}
 
-    println!("Server has stopped");
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        self.store.unpause(self.id);
+    }
 }
 
-async fn dump(State(state): State<Arc<AppState>>, data: Json<Breakpoint>) {
-    let breakpoint = Breakpoint{
-        filepath: data.filepath.clone(),
-        line: data.line.clone(),
-        connector_type: data.connector_type.clone(),
-        payload: data.payload.clone(),
-        timestamp: data.timestamp.clone(),
-        callstack: data.callstack.clone()
+/// Accepts a JSON array of breakpoints. Valid items are stored even when
+/// some of the others are rejected, the response lists the id for every
+/// item (null when rejected) together with the errors.
+async fn dump_batch(State(state): State<Arc<AppState>>, body: Bytes) -> Response {
+    let items: Vec<Value> = match serde_json::from_slice::<Value>(&body) {
+        Ok(Value::Array(items)) => items,
+        Ok(_) => {
+            let rejection = Rejection::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                String::new(),
+                "expected an array of breakpoints".to_string(),
+                &body,
+            );
+            return reject(&state, rejection);
+        }
+        Err(err) => {
+            let rejection =
+                Rejection::new(StatusCode::BAD_REQUEST, String::new(), err.to_string(), &body);
+            return reject(&state, rejection);
+        }
     };
-    state.tx.send(breakpoint).unwrap();
+
+    if !state.store.has_subscribers() {
+        return error_response(&unavailable(&body));
+    }
+
+    let mut ids = vec![];
+    let mut errors = vec![];
+    for (index, item) in items.into_iter().enumerate() {
+        let item_body = item.to_string();
+        let parsed = parse_body(&item, item_body.as_bytes()).and_then(|breakpoint| {
+            refuse_pause(parse_body(item, item_body.as_bytes())?, item_body.as_bytes())?;
+            Ok(breakpoint)
+        });
+        match parsed {
+            Ok(breakpoint) => {
+                ids.push(Some(accept(&state, breakpoint)));
+            }
+            Err(mut rejection) => {
+                rejection.path = prefix_path(&format!("[{}]", index), &rejection.path);
+                errors.push(json!({
+                    "index": index,
+                    "path": rejection.path,
+                    "reason": rejection.reason,
+                }));
+                ids.push(None);
+                state.store.reject(rejection);
+            }
+        }
+    }
+
+    (StatusCode::OK, Json(json!({ "ids": ids, "errors": errors }))).into_response()
+}
+
+/// Accepts a chunked `application/x-ndjson` body, every line is stored as
+/// soon as it's received so the UI doesn't wait for the whole request.
+async fn dump_stream(State(state): State<Arc<AppState>>, mut stream: BodyStream) -> Response {
+    if !state.store.has_subscribers() {
+        return error_response(&unavailable(&[]));
+    }
+
+    let mut buffer: Vec<u8> = vec![];
+    // The buffer holds no newline before this, only new bytes are scanned.
+    let mut scanned = 0;
+    let mut line_number = 0;
+    let mut ids = vec![];
+    let mut errors = vec![];
+
+    loop {
+        let chunk = match stream.next().await {
+            Some(Ok(chunk)) => Some(chunk),
+            Some(Err(err)) => {
+                let rejection =
+                    Rejection::new(StatusCode::BAD_REQUEST, String::new(), err.to_string(), &buffer);
+                return reject(&state, rejection);
+            }
+            None => None,
+        };
+
+        let finished = chunk.is_none();
+        if let Some(chunk) = chunk {
+            buffer.extend_from_slice(&chunk);
+        }
+
+        let mut lines: Vec<Vec<u8>> = vec![];
+        let mut start = 0;
+        while let Some(offset) = buffer[scanned..].iter().position(|byte| *byte == b'\n') {
+            let end = scanned + offset;
+            lines.push(buffer[start..end].to_vec());
+            start = end + 1;
+            scanned = start;
+        }
+        buffer.drain(..start);
+        scanned = buffer.len();
+
+        // Whatever is left after the last newline is the last line.
+        if finished && !buffer.is_empty() {
+            lines.push(std::mem::take(&mut buffer));
+        }
+
+        for line in lines {
+            line_number += 1;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            let deserializer = &mut serde_json::Deserializer::from_slice(&line);
+            let parsed = parse_body(deserializer, &line).and_then(|breakpoint| {
+                let deserializer = &mut serde_json::Deserializer::from_slice(&line);
+                refuse_pause(parse_body(deserializer, &line)?, &line)?;
+                Ok(breakpoint)
+            });
+            match parsed {
+                Ok(breakpoint) => {
+                    ids.push(accept(&state, breakpoint));
+                }
+                Err(mut rejection) => {
+                    errors.push(json!({
+                        "line": line_number,
+                        "path": rejection.path,
+                        "reason": rejection.reason,
+                    }));
+                    rejection.reason = format!("line {}: {}", line_number, rejection.reason);
+                    state.store.reject(rejection);
+                }
+            }
+        }
+
+        if finished {
+            break;
+        }
+
+        // The lines before it are stored, the rest of the stream is refused.
+        if buffer.len() > MAX_STREAM_LINE {
+            let rejection = Rejection::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                String::new(),
+                format!("line {} is longer than {} bytes", line_number + 1, MAX_STREAM_LINE),
+                &buffer,
+            );
+            return reject(&state, rejection);
+        }
+    }
+
+    (StatusCode::OK, Json(json!({ "ids": ids, "errors": errors }))).into_response()
+}
+
+/// Filter a live feed client subscribes with, every field is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Subscription {
+    connector_type: Option<String>,
+    // Glob matched against the whole filepath, e.g. `**/src/*.php`.
+    filepath: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct Filter {
+    connector_type: Option<String>,
+    filepath: Option<glob::Pattern>,
+}
+
+impl Filter {
+    fn new(subscription: Subscription) -> Result<Filter, Rejection> {
+        let filepath = match subscription.filepath {
+            Some(filepath) => Some(glob::Pattern::new(&filepath).map_err(|err| {
+                Rejection::new(
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    "filepath".to_string(),
+                    err.to_string(),
+                    filepath.as_bytes(),
+                )
+            })?),
+            None => None,
+        };
+
+        Ok(Filter {
+            connector_type: subscription.connector_type,
+            filepath,
+        })
+    }
+
+    fn matches(&self, breakpoint: &Breakpoint) -> bool {
+        // `*` stays within a directory, `**` crosses them.
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..glob::MatchOptions::new()
+        };
+
+        self.connector_type
+            .as_ref()
+            .is_none_or(|connector_type| *connector_type == breakpoint.connector_type)
+            && self
+                .filepath
+                .as_ref()
+                .is_none_or(|pattern| pattern.matches_with(&breakpoint.filepath, options))
+    }
+}
+
+/// Pushes every new breakpoint to the client as JSON. The client can send a
+/// subscription at any time, which replaces the previous one.
+async fn live_feed(State(state): State<Arc<AppState>>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| feed(state, socket))
+}
+
+async fn feed(state: Arc<AppState>, mut socket: WebSocket) {
+    let mut breakpoints = state.store.feed();
+    let mut filter = Filter::default();
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let deserializer = &mut serde_json::Deserializer::from_str(&text);
+                    match parse_body(deserializer, text.as_bytes()).and_then(Filter::new) {
+                        Ok(new_filter) => {
+                            filter = new_filter;
+                            json!({ "subscribed": true })
+                        }
+                        Err(rejection) => error_body(&rejection),
+                    }
+                }
+                // Pings are answered by axum, binary messages aren't used.
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+            },
+            breakpoint = breakpoints.recv() => match breakpoint {
+                Ok(stored) if filter.matches(&stored.breakpoint) => json!(*stored),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => json!({
+                    "error": {
+                        "path": "",
+                        "reason": format!("The client is too slow, skipped {} breakpoints.", skipped),
+                    }
+                }),
+                Err(RecvError::Closed) => return,
+            },
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Query string of `GET /breakpoints`, every filter is optional.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BreakpointQuery {
+    connector_type: Option<String>,
+    // Glob, same as the live feed subscriptions.
+    filepath: Option<String>,
+    // Unix timestamps, both inclusive.
+    since: Option<u64>,
+    until: Option<u64>,
+    // Case insensitive text searched in the filepath and the payload.
+    q: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl BreakpointQuery {
+    fn matches_time(&self, breakpoint: &Breakpoint) -> bool {
+        if self.since.is_none() && self.until.is_none() {
+            return true;
+        }
+
+        // Breakpoints without a readable timestamp can't be in any range.
+        match breakpoint.timestamp.trim().parse::<u64>() {
+            Ok(timestamp) => {
+                self.since.is_none_or(|since| timestamp >= since)
+                    && self.until.is_none_or(|until| timestamp <= until)
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn matches_text(&self, breakpoint: &Breakpoint) -> bool {
+        let text = match &self.q {
+            Some(text) => text.to_lowercase(),
+            None => return true,
+        };
+
+        breakpoint.filepath.to_lowercase().contains(&text)
+            || breakpoint.payload.to_string().to_lowercase().contains(&text)
+    }
+}
+
+/// Lists the stored breakpoints, oldest first.
+async fn list_breakpoints(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<BreakpointQuery>, QueryRejection>,
+) -> Response {
+    let Query(query) = match query {
+        Ok(query) => query,
+        Err(err) => {
+            let rejection = Rejection::new(StatusCode::BAD_REQUEST, String::new(), err.body_text(), &[]);
+            return error_response(&rejection);
+        }
+    };
+
+    let subscription = Subscription {
+        connector_type: query.connector_type.clone(),
+        filepath: query.filepath.clone(),
+    };
+    let filter = match Filter::new(subscription) {
+        Ok(filter) => filter,
+        Err(rejection) => return error_response(&rejection),
+    };
+
+    let matching: Vec<Arc<Stored>> = state
+        .store
+        .breakpoints()
+        .into_iter()
+        .filter(|stored| {
+            let breakpoint = &stored.breakpoint;
+            filter.matches(breakpoint) && query.matches_time(breakpoint) && query.matches_text(breakpoint)
+        })
+        .collect();
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let page: Vec<&Stored> = matching
+        .iter()
+        .skip(query.offset)
+        .take(limit)
+        .map(|breakpoint| breakpoint.as_ref())
+        .collect();
+
+    let body = json!({
+        "total": matching.len(),
+        "offset": query.offset,
+        "limit": limit,
+        "breakpoints": page,
+    });
+
+    (StatusCode::OK, Json(body)).into_response()
+}
+
+async fn get_breakpoint(State(state): State<Arc<AppState>>, Path(id): Path<u64>) -> Response {
+    match state.store.breakpoint(id) {
+        Some(breakpoint) => (StatusCode::OK, Json(json!(*breakpoint))).into_response(),
+        None => {
+            let rejection = Rejection::new(
+                StatusCode::NOT_FOUND,
+                String::new(),
+                format!("Breakpoint {} doesn't exist.", id),
+                &[],
+            );
+            error_response(&rejection)
+        }
+    }
+}
+
+async fn clear_breakpoints(State(state): State<Arc<AppState>>) -> Response {
+    let cleared = state.store.clear();
+
+    (StatusCode::OK, Json(json!({ "cleared": cleared }))).into_response()
+}
+
+// Deserializes a request body, the rejection points at the offending field.
+fn parse_body<'de, T, D>(deserializer: D, body: &[u8]) -> Result<T, Rejection>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de, Error = serde_json::Error>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        // Malformed JSON is a bad request, while valid JSON that doesn't
+        // match the breakpoint schema can't be processed.
+        let (status, path) = match err.inner().classify() {
+            Category::Data => (StatusCode::UNPROCESSABLE_ENTITY, err.path().to_string()),
+            _ => (StatusCode::BAD_REQUEST, String::new()),
+        };
+
+        Rejection::new(status, path, err.inner().to_string(), body)
+    })
+}
+
+// Stores the breakpoint and returns its id.
+fn accept(state: &AppState, mut breakpoint: Breakpoint) -> u64 {
+    let payload_error = normalize_payload(&mut breakpoint);
+
+    state.store.push(breakpoint, payload_error)
+}
+
+// Older adapters send the payload as a JSON encoded string, so decode it in
+// place. Strings that don't look like JSON are kept as plain strings, while
+// for broken objects and arrays the parse error is returned.
+fn normalize_payload(breakpoint: &mut Breakpoint) -> Option<String> {
+    let raw = match &breakpoint.payload {
+        Value::String(raw) => raw.clone(),
+        _ => return None,
+    };
+
+    if raw.is_empty() || raw == "No variables" {
+        breakpoint.payload = Value::Null;
+        return None;
+    }
+
+    match serde_json::from_str::<Value>(&raw) {
+        Ok(value) => {
+            breakpoint.payload = value;
+            None
+        }
+        Err(err) => {
+            let trimmed = raw.trim_start();
+            if trimmed.starts_with('{') || trimmed.starts_with('[') {
+                Some(err.to_string())
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// A batch or stream can't be held open for one of its breakpoints, so
+// they are refused instead of silently not pausing.
+fn refuse_pause(request: PauseRequest, body: &[u8]) -> Result<(), Rejection> {
+    if !request.pause {
+        return Ok(());
+    }
+
+    Err(Rejection::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "pause".to_string(),
+        "only /dump and /break can pause".to_string(),
+        body,
+    ))
+}
+
+fn prefix_path(prefix: &str, path: &str) -> String {
+    if path.is_empty() || path == "." {
+        prefix.to_string()
+    } else {
+        format!("{}.{}", prefix, path)
+    }
+}
+
+fn unavailable(body: &[u8]) -> Rejection {
+    Rejection::new(
+        StatusCode::SERVICE_UNAVAILABLE,
+        String::new(),
+        "The UI is not consuming breakpoints.".to_string(),
+        body,
+    )
+}
+
+// Reports the rejection to the UI and builds the error response for the adapter.
+fn reject(state: &AppState, rejection: Rejection) -> Response {
+    let response = error_response(&rejection);
+    state.store.reject(rejection);
+
+    response
+}
+
+fn error_response(rejection: &Rejection) -> Response {
+    let status = StatusCode::from_u16(rejection.status).unwrap_or(StatusCode::BAD_REQUEST);
+
+    (status, Json(error_body(rejection))).into_response()
+}
+
+fn error_body(rejection: &Rejection) -> Value {
+    json!({
+        "error": {
+            "path": rejection.path,
+            "reason": rejection.reason,
+        }
+    })
 }
@@ -0,0 +1,150 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{config, store::Stored};
+
+/// An on-disk debugging session.
+///
+/// Every breakpoint is appended as a single JSON line, so a crash loses at
+/// most the line that was being written.
+#[derive(Debug)]
+pub struct Session {
+    pub name: String,
+    path: PathBuf,
+    // Dropped once a write fails, the breakpoints are then only kept in memory.
+    file: Option<File>,
+    error: Option<String>,
+}
+
+impl Session {
+    /// Opens the session with the given name, or starts a new one named
+    /// after the current time.
+    pub fn open(name: Option<String>) -> Session {
+        let name = name.unwrap_or_else(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("System time is before the UNIX epoch.");
+            now.as_secs().to_string()
+        });
+
+        let dir = get_sessions_dir();
+        fs::create_dir_all(&dir).expect("Could not create the sessions directory.");
+
+        let path = dir.join(format!("{}.jsonl", name));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .expect("Could not open the session file.");
+
+        Session {
+            name,
+            path,
+            file: Some(file),
+            error: None,
+        }
+    }
+
+    /// Starts an empty session in the temporary directory.
+    #[cfg(test)]
+    pub fn temporary(name: &str) -> Session {
+        let path = std::env::temp_dir().join(format!("pmd-{}-{}.jsonl", name, std::process::id()));
+        let file = File::create(&path).expect("Could not create the session file.");
+
+        Session {
+            name: name.to_string(),
+            path,
+            file: Some(file),
+            error: None,
+        }
+    }
+
+    /// Reads back all the breakpoints stored in the session.
+    pub fn load(&self) -> Vec<Stored> {
+        let file = File::open(&self.path).expect("Could not read the session file.");
+
+        // A line that can't be parsed was most likely cut off by a crash,
+        // so skip it instead of losing the whole session.
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect()
+    }
+
+    /// Appends the breakpoint. The first failure stops writing to the file,
+    /// instead of failing every breakpoint after it.
+    pub fn append(&mut self, breakpoint: &Stored) {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => return,
+        };
+
+        let written = serde_json::to_string(breakpoint)
+            .map_err(|err| err.to_string())
+            .and_then(|line| writeln!(file, "{}", line).map_err(|err| err.to_string()));
+
+        if let Err(err) = written {
+            self.file = None;
+            self.error = Some(format!(
+                "Stopped saving the session to {}: {}",
+                self.path.display(),
+                err
+            ));
+        }
+    }
+
+    /// Replaces the saved breakpoints, e.g. after they were cleared. The new
+    /// file is written next to it first, so a crash keeps the old one.
+    pub fn rewrite<'a>(&mut self, breakpoints: impl Iterator<Item = &'a Stored>) {
+        if self.file.is_none() {
+            return;
+        }
+
+        let temporary = self.path.with_extension("jsonl.tmp");
+        let written = write_all(&temporary, breakpoints)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .and_then(|_| OpenOptions::new().append(true).open(&self.path));
+
+        match written {
+            Ok(file) => self.file = Some(file),
+            Err(err) => {
+                let _ = fs::remove_file(&temporary);
+                self.file = None;
+                self.error = Some(format!(
+                    "Stopped saving the session to {}: {}",
+                    self.path.display(),
+                    err
+                ));
+            }
+        }
+    }
+
+    /// Why the session is no longer saved, if it isn't.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+fn write_all<'a>(path: &Path, breakpoints: impl Iterator<Item = &'a Stored>) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for breakpoint in breakpoints {
+        serde_json::to_writer(&mut file, breakpoint)?;
+        writeln!(file)?;
+    }
+
+    file.into_inner().map_err(|err| err.into_error())?.sync_all()
+}
+
+fn get_sessions_dir() -> PathBuf {
+    let config_path = config::get_config_path();
+    let config_dir = config_path
+        .parent()
+        .expect("Config path has no parent directory.");
+
+    config_dir.join("sessions")
+}
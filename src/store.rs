@@ -0,0 +1,561 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    sync::{broadcast, oneshot, watch, Notify},
+    time::{timeout_at, Instant},
+};
+
+use crate::{
+    config::PathMapping,
+    server::{Breakpoint, Command, CommandKind, Rejection, Reply},
+    session::Session,
+};
+
+// How many breakpoints a slow feed subscriber can fall behind.
+const FEED_CAPACITY: usize = 1024;
+
+// How many of the latest rejected requests are kept for the UI, the stats
+// still count all of them.
+const MAX_REJECTED: usize = 100;
+
+/// Holds every breakpoint received by the server and is shared with the
+/// front ends, which are notified whenever something changes.
+///
+/// The server never waits on a front end, so a burst of dumps can't block
+/// or crash it. Once `limit` breakpoints are kept in memory the oldest ones
+/// are shed, they are still available in the session file.
+#[derive(Debug)]
+pub struct Store {
+    inner: Mutex<Inner>,
+    // Bumped on every change.
+    version: watch::Sender<u64>,
+    // Wakes the adapters waiting for commands.
+    commands_changed: Notify,
+    // Every new breakpoint, for the live feed.
+    feed: broadcast::Sender<Arc<Stored>>,
+    limit: usize,
+    // Applied to every pushed breakpoint, the first matching one wins.
+    path_mappings: Vec<PathMapping>,
+}
+
+/// A received breakpoint together with what the server keeps about it.
+/// Adapters only send the `Breakpoint`, none of the rest is read from a
+/// request.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Stored {
+    // Assigned when the breakpoint is received.
+    pub id: u64,
+    // With the paths mapped to local files.
+    #[serde(flatten)]
+    pub breakpoint: Breakpoint,
+    // The path as sent by the adapter, when a path mapping changed it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_filepath: Option<String>,
+    // Same for every frame of the callstack, empty when none was mapped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub original_callstack: Vec<Option<String>>,
+    // Set when a legacy string payload could not be parsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_error: Option<String>,
+}
+
+/// A frame of the callstack of a stored breakpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub filepath: String,
+    // The path as sent by the adapter, when a path mapping changed it.
+    pub original_filepath: Option<String>,
+    pub line: String,
+}
+
+impl Stored {
+    /// Wraps a breakpoint whose paths weren't mapped yet.
+    pub fn new(id: u64, breakpoint: Breakpoint, payload_error: Option<String>) -> Stored {
+        Stored {
+            id,
+            breakpoint,
+            original_filepath: None,
+            original_callstack: vec![],
+            payload_error,
+        }
+    }
+
+    /// The path as the adapter sent it, for display.
+    pub fn display_filepath(&self) -> &str {
+        self.original_filepath.as_deref().unwrap_or(&self.breakpoint.filepath)
+    }
+
+    /// The callstack together with the paths the adapter sent.
+    pub fn frames(&self) -> Vec<Frame> {
+        self.breakpoint
+            .callstack
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| Frame {
+                filepath: frame.filepath.clone(),
+                original_filepath: self.original_callstack.get(index).cloned().flatten(),
+                line: frame.line.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Frame {
+    /// The path as the adapter sent it, for display.
+    pub fn display_filepath(&self) -> &str {
+        self.original_filepath.as_deref().unwrap_or(&self.filepath)
+    }
+}
+
+/// How the user resumed a paused breakpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    Abort,
+    // Only debugger engines, like DBGp, can step.
+    StepInto,
+    StepOver,
+    StepOut,
+}
+
+impl Resume {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resume::Continue => "continue",
+            Resume::Abort => "abort",
+            Resume::StepInto => "step_into",
+            Resume::StepOver => "step_over",
+            Resume::StepOut => "step_out",
+        }
+    }
+
+    /// Whether breakpoints of the connector can honour this. The HTTP
+    /// adapters can only continue or abort.
+    pub fn supported_by(&self, connector_type: &str) -> bool {
+        match self {
+            Resume::Continue | Resume::Abort => true,
+            Resume::StepInto | Resume::StepOver | Resume::StepOut => connector_type == "dbgp",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    breakpoints: VecDeque<Arc<Stored>>,
+    rejected: VecDeque<Rejection>,
+    next_id: u64,
+    stats: Stats,
+    session: Session,
+    // Breakpoints that hold their adapter until the user continues.
+    paused: HashMap<u64, oneshot::Sender<Resume>>,
+    // Commands waiting to be picked up by the adapter, per breakpoint.
+    commands: HashMap<u64, VecDeque<Command>>,
+    // Commands still waiting for a reply with their breakpoint id, dropped
+    // once the reply arrives or the breakpoint is no longer paused.
+    issued: HashMap<u64, (u64, Command)>,
+    replies: HashMap<u64, Vec<Reply>>,
+    next_command_id: u64,
+    // Set once the process started with `pmd run` has exited.
+    exit_code: Option<i32>,
+}
+
+impl Inner {
+    // Drops the queued and unanswered commands of a breakpoint that is no
+    // longer paused.
+    fn forget_commands(&mut self, breakpoint_id: u64) {
+        self.commands.remove(&breakpoint_id);
+        self.issued.retain(|_, (id, _)| *id != breakpoint_id);
+    }
+}
+
+/// Result of waiting for a command.
+#[derive(Debug)]
+pub enum NextCommand {
+    Command(Command),
+    // Nothing was sent in time, the adapter should ask again.
+    Empty,
+    // The breakpoint is no longer paused.
+    Resumed,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub received: u64,
+    pub shed: u64,
+    pub rejected: u64,
+    pub unauthorized: u64,
+    pub replies: u64,
+}
+
+impl Store {
+    pub fn new(session: Session, limit: usize, path_mappings: Vec<PathMapping>) -> Store {
+        // Mapped again, the mappings may have changed since they were saved.
+        let mut breakpoints: VecDeque<Arc<Stored>> = session
+            .load()
+            .into_iter()
+            .map(|mut breakpoint| {
+                // The mappings may have changed since, start from the paths
+                // the adapter sent.
+                unmap_paths(&mut breakpoint);
+                map_paths(&path_mappings, &mut breakpoint);
+                Arc::new(breakpoint)
+            })
+            .collect();
+        let next_id = breakpoints.iter().map(|b| b.id).max().unwrap_or(0) + 1;
+
+        let mut stats = Stats {
+            received: breakpoints.len() as u64,
+            ..Stats::default()
+        };
+        while breakpoints.len() > limit {
+            breakpoints.pop_front();
+            stats.shed += 1;
+        }
+
+        let (version, _) = watch::channel(0);
+        let (feed, _) = broadcast::channel(FEED_CAPACITY);
+
+        Store {
+            inner: Mutex::new(Inner {
+                breakpoints,
+                rejected: VecDeque::new(),
+                next_id,
+                stats,
+                session,
+                paused: HashMap::new(),
+                commands: HashMap::new(),
+                issued: HashMap::new(),
+                replies: HashMap::new(),
+                next_command_id: 1,
+                exit_code: None,
+            }),
+            version,
+            commands_changed: Notify::new(),
+            feed,
+            limit,
+            path_mappings,
+        }
+    }
+
+    /// Returns a receiver that is marked as changed on every update.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+
+    /// Returns a receiver for the breakpoints pushed from now on. A receiver
+    /// that falls too far behind skips the oldest ones.
+    pub fn feed(&self) -> broadcast::Receiver<Arc<Stored>> {
+        self.feed.subscribe()
+    }
+
+    /// Whether any front end is listening for changes.
+    pub fn has_subscribers(&self) -> bool {
+        self.version.receiver_count() > 0
+    }
+
+    /// Stores the breakpoint and returns the id assigned to it.
+    pub fn push(&self, breakpoint: Breakpoint, payload_error: Option<String>) -> u64 {
+        let mut breakpoint = Stored::new(0, breakpoint, payload_error);
+        map_paths(&self.path_mappings, &mut breakpoint);
+        let mut inner = self.inner.lock().unwrap();
+
+        breakpoint.id = inner.next_id;
+        inner.next_id += 1;
+        inner.session.append(&breakpoint);
+
+        let breakpoint = Arc::new(breakpoint);
+        inner.breakpoints.push_back(Arc::clone(&breakpoint));
+        inner.stats.received += 1;
+        while inner.breakpoints.len() > self.limit {
+            if let Some(shed) = inner.breakpoints.pop_front() {
+                // A paused one still gets replies until it's resumed.
+                if !inner.paused.contains_key(&shed.id) {
+                    inner.replies.remove(&shed.id);
+                }
+            }
+            inner.stats.shed += 1;
+        }
+
+        let id = inner.next_id - 1;
+        drop(inner);
+
+        // Nobody listening to the feed isn't an error.
+        let _ = self.feed.send(breakpoint);
+        self.notify();
+        id
+    }
+
+    /// Removes the stored breakpoints and returns how many were removed.
+    /// Paused ones are kept, their adapters are still waiting on them. Ids
+    /// keep increasing and the session file only keeps the paused ones too.
+    pub fn clear(&self) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let before = inner.breakpoints.len();
+
+        let Inner {
+            breakpoints,
+            paused,
+            replies,
+            issued,
+            session,
+            ..
+        } = &mut *inner;
+        breakpoints.retain(|breakpoint| paused.contains_key(&breakpoint.id));
+        replies.retain(|id, _| paused.contains_key(id));
+        issued.retain(|_, (id, _)| paused.contains_key(id));
+        session.rewrite(breakpoints.iter().map(|breakpoint| breakpoint.as_ref()));
+
+        let removed = before - inner.breakpoints.len();
+        drop(inner);
+
+        self.notify();
+        removed
+    }
+
+    pub fn reject(&self, rejection: Rejection) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.rejected.push_back(rejection);
+        if inner.rejected.len() > MAX_REJECTED {
+            inner.rejected.pop_front();
+        }
+        inner.stats.rejected += 1;
+        drop(inner);
+
+        self.notify();
+    }
+
+    /// Counts a request that was refused because of a missing or wrong token.
+    pub fn unauthorized(&self) {
+        self.inner.lock().unwrap().stats.unauthorized += 1;
+        self.notify();
+    }
+
+    /// Marks the breakpoint as paused, the receiver completes once the user
+    /// resumes it.
+    pub fn pause(&self, id: u64) -> oneshot::Receiver<Resume> {
+        let (tx, rx) = oneshot::channel();
+        self.inner.lock().unwrap().paused.insert(id, tx);
+        self.notify();
+
+        rx
+    }
+
+    /// Resumes a paused breakpoint, returns false if it wasn't paused.
+    pub fn resume(&self, id: u64, resume: Resume) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let resumed = match inner.paused.remove(&id) {
+            Some(tx) => tx.send(resume).is_ok(),
+            None => false,
+        };
+        inner.forget_commands(id);
+        drop(inner);
+
+        self.notify();
+        resumed
+    }
+
+    /// Drops the pause without resuming, e.g. when the adapter timed out.
+    pub fn unpause(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        let removed = inner.paused.remove(&id).is_some();
+        inner.forget_commands(id);
+        drop(inner);
+
+        if removed {
+            self.notify();
+        }
+    }
+
+    pub fn paused(&self) -> HashSet<u64> {
+        self.inner.lock().unwrap().paused.keys().copied().collect()
+    }
+
+    /// Queues a command for the adapter holding the paused breakpoint.
+    pub fn send_command(&self, breakpoint_id: u64, kind: CommandKind) -> Option<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.paused.contains_key(&breakpoint_id) {
+            return None;
+        }
+
+        let id = inner.next_command_id;
+        inner.next_command_id += 1;
+
+        let command = Command { id, kind };
+        inner.issued.insert(id, (breakpoint_id, command.clone()));
+        inner
+            .commands
+            .entry(breakpoint_id)
+            .or_default()
+            .push_back(command);
+        drop(inner);
+
+        self.commands_changed.notify_waiters();
+        Some(id)
+    }
+
+    /// Waits up to `wait` for the next command of a paused breakpoint.
+    pub async fn next_command(&self, breakpoint_id: u64, wait: Duration) -> NextCommand {
+        let deadline = Instant::now() + wait;
+
+        loop {
+            // Register before checking, so a command sent in between isn't missed.
+            let changed = self.commands_changed.notified();
+
+            {
+                let mut inner = self.inner.lock().unwrap();
+                if !inner.paused.contains_key(&breakpoint_id) {
+                    return NextCommand::Resumed;
+                }
+
+                let command = inner
+                    .commands
+                    .get_mut(&breakpoint_id)
+                    .and_then(|commands| commands.pop_front());
+                if let Some(command) = command {
+                    return NextCommand::Command(command);
+                }
+            }
+
+            if timeout_at(deadline, changed).await.is_err() {
+                return NextCommand::Empty;
+            }
+        }
+    }
+
+    /// Stores the adapter's reply to a command. Returns false when the
+    /// command wasn't sent to this breakpoint.
+    pub fn reply(&self, breakpoint_id: u64, command_id: u64, result: Value, error: Option<String>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let command = match inner.issued.remove(&command_id) {
+            Some((id, command)) if id == breakpoint_id => command,
+            Some(issued) => {
+                // Sent to another breakpoint, it may still get its reply.
+                inner.issued.insert(command_id, issued);
+                return false;
+            }
+            None => return false,
+        };
+
+        let reply = Reply {
+            command,
+            result,
+            error,
+        };
+        inner.replies.entry(breakpoint_id).or_default().push(reply);
+        inner.stats.replies += 1;
+        drop(inner);
+
+        self.notify();
+        true
+    }
+
+    pub fn replies(&self, breakpoint_id: u64) -> Vec<Reply> {
+        self.inner
+            .lock()
+            .unwrap()
+            .replies
+            .get(&breakpoint_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_exit_code(&self, code: i32) {
+        self.inner.lock().unwrap().exit_code = Some(code);
+        self.notify();
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        self.inner.lock().unwrap().exit_code
+    }
+
+    pub fn breakpoint(&self, id: u64) -> Option<Arc<Stored>> {
+        let inner = self.inner.lock().unwrap();
+
+        // Ids are increasing, so the breakpoints are sorted by id.
+        let index = inner.breakpoints.binary_search_by_key(&id, |b| b.id).ok()?;
+        inner.breakpoints.get(index).cloned()
+    }
+
+    pub fn breakpoints(&self) -> Vec<Arc<Stored>> {
+        self.inner.lock().unwrap().breakpoints.iter().cloned().collect()
+    }
+
+    /// Returns the breakpoints with an id greater than `id`, oldest first.
+    pub fn breakpoints_since(&self, id: u64) -> Vec<Arc<Stored>> {
+        let inner = self.inner.lock().unwrap();
+        let mut breakpoints: Vec<Arc<Stored>> = inner
+            .breakpoints
+            .iter()
+            .rev()
+            .take_while(|breakpoint| breakpoint.id > id)
+            .cloned()
+            .collect();
+        breakpoints.reverse();
+
+        breakpoints
+    }
+
+    /// The latest rejected requests, oldest first.
+    pub fn rejected(&self) -> Vec<Rejection> {
+        self.inner.lock().unwrap().rejected.iter().cloned().collect()
+    }
+
+    pub fn session_error(&self) -> Option<String> {
+        self.inner.lock().unwrap().session.error().map(String::from)
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.inner.lock().unwrap().stats
+    }
+
+    fn notify(&self) {
+        self.version.send_modify(|version| *version += 1);
+        // Adapters waiting for commands need to know when a pause ends.
+        self.commands_changed.notify_waiters();
+    }
+}
+
+// Points the breakpoint and its callstack to the local files, keeping the
+// paths the adapter sent.
+fn map_paths(path_mappings: &[PathMapping], stored: &mut Stored) {
+    let breakpoint = &mut stored.breakpoint;
+    let connector_type = breakpoint.connector_type.as_str();
+    let map = |path: &str| {
+        path_mappings
+            .iter()
+            .find_map(|mapping| mapping.map(connector_type, path))
+    };
+
+    if let Some(local) = map(&breakpoint.filepath) {
+        stored.original_filepath = Some(std::mem::replace(&mut breakpoint.filepath, local));
+    }
+
+    let originals: Vec<Option<String>> = breakpoint
+        .callstack
+        .iter_mut()
+        .map(|frame| map(&frame.filepath).map(|local| std::mem::replace(&mut frame.filepath, local)))
+        .collect();
+    if originals.iter().any(Option::is_some) {
+        stored.original_callstack = originals;
+    }
+}
+
+// Puts back the paths the adapter sent on a breakpoint mapped before.
+fn unmap_paths(stored: &mut Stored) {
+    if let Some(original) = stored.original_filepath.take() {
+        stored.breakpoint.filepath = original;
+    }
+
+    let originals = std::mem::take(&mut stored.original_callstack);
+    for (frame, original) in stored.breakpoint.callstack.iter_mut().zip(originals) {
+        if let Some(original) = original {
+            frame.filepath = original;
+        }
+    }
+}
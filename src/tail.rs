@@ -0,0 +1,134 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::Arc,
+};
+
+use crossterm::style::{StyledContent, Stylize};
+use tokio::{signal, sync::watch};
+
+use crate::{
+    store::Stored,
+    store::{Resume, Store},
+};
+
+/// Prints every breakpoint to stdout as it arrives, for places where the
+/// TUI can't run, like CI containers or a plain log pane.
+///
+/// Breakpoints of a reopened session are printed first. Nobody can continue
+/// a paused breakpoint here, so those are resumed right away.
+#[tokio::main(worker_threads = 1)]
+pub async fn run(store: Arc<Store>, mut changes: watch::Receiver<u64>, json: bool) {
+    let printer = Printer {
+        json,
+        colors: io::stdout().is_terminal(),
+    };
+    let mut last_id = 0;
+    let mut rejected = 0;
+    let mut session_failed = false;
+
+    loop {
+        for breakpoint in store.breakpoints_since(last_id) {
+            // Breakpoints shed before they were printed are only in the session file.
+            if last_id != 0 && breakpoint.id > last_id + 1 {
+                eprintln!("Skipped {} breakpoints, see the session file.", breakpoint.id - last_id - 1);
+            }
+            last_id = breakpoint.id;
+
+            if printer.print(&breakpoint).is_err() {
+                // Stdout was closed, e.g. the pipe to `head` ended.
+                return;
+            }
+        }
+
+        for paused in store.paused() {
+            store.resume(paused, Resume::Continue);
+        }
+
+        // Only the latest rejections are kept, the count covers all of them.
+        let total = store.stats().rejected;
+        if total > rejected {
+            let rejections = store.rejected();
+            let new = ((total - rejected) as usize).min(rejections.len());
+            for rejection in &rejections[rejections.len() - new..] {
+                eprintln!(
+                    "Rejected a request ({}): {} {}",
+                    rejection.status, rejection.path, rejection.reason
+                );
+            }
+            rejected = total;
+        }
+
+        if !session_failed {
+            if let Some(error) = store.session_error() {
+                eprintln!("{}", error);
+                session_failed = true;
+            }
+        }
+
+        // Everything is printed, so `pmd run` can exit with its command.
+        if store.exit_code().is_some() {
+            return;
+        }
+
+        tokio::select! {
+            changed = changes.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            _ = signal::ctrl_c() => return,
+        }
+    }
+}
+
+struct Printer {
+    json: bool,
+    colors: bool,
+}
+
+impl Printer {
+    fn print(&self, stored: &Stored) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+
+        if self.json {
+            let line = serde_json::to_string(stored).expect("Could not serialize the breakpoint.");
+            writeln!(stdout, "{}", line)?;
+            return stdout.flush();
+        }
+
+        let breakpoint = &stored.breakpoint;
+        writeln!(
+            stdout,
+            "{} {} {}:{} {}",
+            self.paint(format!("#{}", stored.id), Stylize::dark_grey),
+            self.paint(format!("[{}]", breakpoint.connector_type), Stylize::magenta),
+            self.paint(breakpoint.filepath.clone(), Stylize::cyan),
+            self.paint(breakpoint.line.clone(), Stylize::yellow),
+            self.paint(breakpoint.timestamp.clone(), Stylize::dark_grey),
+        )?;
+
+        for frame in &breakpoint.callstack {
+            writeln!(stdout, "  at {}:{}", frame.filepath, frame.line)?;
+        }
+
+        if let Some(error) = &stored.payload_error {
+            writeln!(stdout, "{}", self.paint(error.clone(), Stylize::red))?;
+        } else if !breakpoint.payload.is_null() {
+            let payload = serde_json::to_string_pretty(&breakpoint.payload)
+                .expect("Could not serialize the payload.");
+            writeln!(stdout, "{}", payload)?;
+        }
+
+        writeln!(stdout)?;
+        stdout.flush()
+    }
+
+    // Colors are left out when stdout isn't a terminal.
+    fn paint(&self, text: String, style: fn(String) -> StyledContent<String>) -> String {
+        if self.colors {
+            style(text).to_string()
+        } else {
+            text
+        }
+    }
+}
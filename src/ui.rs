@@ -1,14 +1,17 @@
-use std::{io, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use std::{collections::HashSet, io, sync::Arc, time::{Duration, Instant}};
 
-use crossterm::{terminal::enable_raw_mode, event::poll};
-use tokio::sync::broadcast::Receiver;
+use crossterm::{terminal::{disable_raw_mode, enable_raw_mode}, event::EventStream};
+use futures_util::StreamExt;
+use tokio::{sync::watch, time::{interval, MissedTickBehavior}};
 use tui::{backend::CrosstermBackend, Terminal};
 use tui_tree_widget::{TreeItem, TreeState};
 
-use crate::{server::Breakpoint, ui::{keymap::{InputMode, ListMode}, layout::{BreakpointList, CallstackList}}, config::Config};
+use crate::{server::Rejection, store::{Stats, Store, Stored}, ui::{keymap::{InputMode, ListMode}, layout::{payload::RawPayload, source::SourcePreview, tree, BreakpointList, CallstackList}, search::Filter}, config::Config};
 
+mod editor;
 mod keymap;
 mod layout;
+mod search;
 
 // Our shared state
 #[derive(Debug)]
@@ -19,8 +22,38 @@ pub struct UiState<'a> {
     pub input_mode: InputMode, 
     pub popup: Popup,
     pub status_bar: StatusBar,
+    pub rejected: Vec<Rejection>,
+    pub stats: Stats,
+    // Ids of the breakpoints that hold their adapter.
+    pub paused: HashSet<u64>,
+    // Exit code of the process started with `pmd run`, once it exited.
+    pub exit_code: Option<i32>,
+    // Set once saving the session failed and the user was told.
+    pub session_failed: bool,
+    // File shown in the source pane.
+    pub source: SourcePreview,
+    // Pretty printed payload, shown instead of the inspection tree.
+    pub raw_payload: RawPayload,
+    pub store: Arc<Store>,
     pub leader_tick_time: Instant,
+    // Set by the leader key, cleared by the key after it.
+    pub leader_pending: bool,
     pub config: Config,
+    // Text typed in command mode.
+    pub command_line: String,
+    // Set when the user asked to quit.
+    pub quit: bool,
+    // Query typed in search mode and the filter parsed from it.
+    pub search: String,
+    pub filter: Filter,
+    // Whether the list only shows the matches, `n`/`N` jump between them
+    // either way.
+    pub filtered: bool,
+    // How many of the stored breakpoints match the search.
+    pub matching: usize,
+    // File and line to open in the editor, picked up by the render loop,
+    // which owns the event stream the editor has to take over from.
+    pub open_in_editor: Option<(String, String)>,
 }
 
 #[derive(Debug)]
@@ -119,10 +152,12 @@ impl<'a> StatefulTree<'a> {
         }
     }
 
+    #[allow(dead_code)]
     pub fn first(&mut self) {
         self.state.select_first();
     }
 
+    #[allow(dead_code)]
     pub fn last(&mut self) {
         self.state.select_last(&self.items);
     }
@@ -143,6 +178,7 @@ impl<'a> StatefulTree<'a> {
         self.state.key_right();
     }
 
+    #[allow(dead_code)]
     pub fn toggle(&mut self) {
         self.state.toggle_selected();
     }
@@ -150,23 +186,143 @@ impl<'a> StatefulTree<'a> {
 
 
 impl<'a> UiState<'a> {
-    pub fn new(config: Config) -> UiState<'a> {
+    pub fn new(config: Config, store: Arc<Store>) -> UiState<'a> {
         UiState {
             list_state: ListState::new(),
             tree_state: StatefulTree::with_items(vec![]),
             popup: Popup::new(),
             status_bar: StatusBar::new(),
+            rejected: vec![],
+            stats: Stats::default(),
+            paused: HashSet::new(),
+            exit_code: None,
+            session_failed: false,
+            source: SourcePreview::new(),
+            raw_payload: RawPayload::new(),
+            store,
             input_mode: InputMode::Normal,
             leader_tick_time: Instant::now(),
+            leader_pending: false,
             list_mode: ListMode::Breakpoint,
             config,
+            command_line: String::new(),
+            quit: false,
+            search: String::new(),
+            filter: Filter::default(),
+            filtered: false,
+            matching: 0,
+            open_in_editor: None,
+        }
+    }
+
+    /// Rebuilds the Inspection tree for the selected breakpoint.
+    pub fn refresh_tree(&mut self) {
+        let breakpoint = self.list_state.breakpoint.get_selected_breakpoint();
+        let replies = match breakpoint {
+            Some(breakpoint) => self.store.replies(breakpoint.id),
+            None => vec![],
+        };
+
+        self.tree_state.items = tree::build_tree_items(breakpoint, &replies);
+    }
+
+    /// Shows the stored breakpoints that match the search. Returns whether
+    /// another breakpoint got selected, the tree and callstack are rebuilt
+    /// for it then.
+    pub fn refresh_list(&mut self) -> bool {
+        let breakpoints = self.store.breakpoints();
+        self.filter.retain(&breakpoints);
+
+        let matches: Vec<Arc<Stored>> = breakpoints
+            .iter()
+            .filter(|breakpoint| self.filter.matches(breakpoint))
+            .cloned()
+            .collect();
+        self.matching = matches.len();
+        let items = if self.filtered { matches } else { breakpoints };
+
+        let changed = self.list_state.breakpoint.set_items(items);
+        if changed {
+            self.refresh_tree();
+            self.list_state.callstack.items = self.list_state.breakpoint.get_callstack();
+        }
+
+        changed
+    }
+
+    /// Filters the breakpoint list with the query typed in search mode.
+    pub fn set_search(&mut self, query: String) {
+        self.filter.set_query(&query);
+        self.search = query;
+        self.filtered = true;
+        self.refresh_list();
+    }
+
+    /// The selected breakpoint when it's paused, otherwise the latest paused one.
+    pub fn target_paused_breakpoint(&mut self) -> Option<u64> {
+        let selected = self
+            .list_state
+            .breakpoint
+            .get_selected_breakpoint()
+            .map(|breakpoint| breakpoint.id)
+            .filter(|id| self.paused.contains(id));
+
+        selected.or_else(|| self.paused.iter().max().copied())
+    }
+
+    /// Pulls the latest data from the store.
+    pub fn sync_with_store(&mut self) {
+        let previous_stats = self.stats;
+        self.stats = self.store.stats();
+        if self.stats.rejected != previous_stats.rejected {
+            self.rejected = self.store.rejected();
+        }
+
+        // The breakpoints can be cleared through the API or shed.
+        if !self.refresh_list() && self.stats.replies != previous_stats.replies {
+            self.refresh_tree();
+        }
+
+        let paused = self.store.paused();
+        let newly_paused = paused.difference(&self.paused).max().copied();
+        if let Some(id) = newly_paused {
+            // Looked up in the store, the search may hide it.
+            if let Some(stored) = self.store.breakpoint(id) {
+                self.status_bar.set_status(format!(
+                    "Paused at [{}] {}, press c to continue",
+                    stored.breakpoint.line,
+                    stored.display_filepath()
+                ));
+            }
+        }
+        self.paused = paused;
+
+        if !self.session_failed {
+            if let Some(error) = self.store.session_error() {
+                self.status_bar.set_status(error);
+                self.session_failed = true;
+            }
+        }
+
+        let exit_code = self.store.exit_code();
+        if exit_code != self.exit_code {
+            if let Some(code) = exit_code {
+                self.status_bar
+                    .set_status(format!("The process exited with code {}, press q to quit", code));
+            }
+            self.exit_code = exit_code;
         }
     }
 }
 
 #[tokio::main(worker_threads = 1)]
 #[allow(unused_must_use)]
-pub async fn render(mut rx: Receiver<Breakpoint>, config: Config) {
+pub async fn render(
+    store: Arc<Store>,
+    mut changes: watch::Receiver<u64>,
+    config: Config,
+    session_name: String,
+) {
     // Set up terminal output
     enable_raw_mode().unwrap();
     let stdout = io::stdout();
@@ -176,30 +332,70 @@ pub async fn render(mut rx: Receiver<Breakpoint>, config: Config) {
     // Clear the terminal before first draw.
     terminal.clear().unwrap();
 
-    let data = Arc::new(Mutex::new(vec![]));
-    let server_msgs = Arc::clone(&data);
-    let tui_msgs = Arc::clone(&data);
+    // Create a new ui state.
+    let mut state = UiState::new(config, store);
+    state
+        .status_bar
+        .set_status(format!("Session: {}", session_name));
+    state.sync_with_store();
 
-    tokio::spawn(async move {
-        loop {
-            let msg = rx.recv().await.unwrap();
-            server_msgs.lock().unwrap().push(msg);
-        }
-    });
+    let mut events = EventStream::new();
+    // New breakpoints are picked up on the next tick, so a burst of dumps
+    // results in one redraw per tick instead of one per dump.
+    let mut ticks = interval(Duration::from_millis(50));
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    let mut store_changed = false;
+    let mut redraw = true;
 
-    // Create a new ui state.
-    let mut state = UiState::new(config);
     loop {
-        if poll(Duration::from_millis(1)).unwrap() {
-            // It's guaranteed that `read` won't block, because `poll` returned
-            // `Ok(true)`.
-            keymap::handle_keymap_event(&mut state, &mut terminal);
+        if redraw {
+            // Lock the terminal and start a drawing session.
+            terminal.draw(|f| {
+                layout::render_main(&mut state, f);
+            }).unwrap();
+            redraw = false;
         }
 
-        // Lock the terminal and start a drawing session.
-        terminal.draw(|f| {
-            state.list_state.breakpoint.items = tui_msgs.lock().unwrap().to_vec();
-            layout::render_main(&mut state, f);
-        }).unwrap();
+        tokio::select! {
+            event = events.next() => {
+                match event {
+                    Some(Ok(event)) => {
+                        keymap::handle_keymap_event(event, &mut state, &mut terminal);
+                        if state.quit {
+                            break;
+                        }
+                        if let Some((file, line)) = state.open_in_editor.take() {
+                            // The stream keeps reading the terminal in the
+                            // background, it would steal the editor's input.
+                            drop(events);
+                            let result = editor::open(&state.config.editor, &file, &line, &mut terminal);
+                            events = EventStream::new();
+
+                            match result {
+                                Ok(message) | Err(message) => state.status_bar.set_status(message),
+                            }
+                        }
+                        redraw = true;
+                    }
+                    // The terminal is gone, nothing left to render to.
+                    Some(Err(_)) | None => break,
+                }
+            }
+            changed = changes.changed(), if !store_changed => {
+                if changed.is_err() {
+                    break;
+                }
+                store_changed = true;
+            }
+            _ = ticks.tick(), if store_changed => {
+                state.sync_with_store();
+                store_changed = false;
+                redraw = true;
+            }
+        }
     }
+
+    disable_raw_mode().unwrap();
+    terminal.show_cursor().unwrap();
 }
@@ -0,0 +1,159 @@
+use std::{
+    env,
+    io::{self, Stdout},
+    process::{Command, Stdio},
+    thread,
+};
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use tui::{backend::CrosstermBackend, Terminal};
+
+use crate::config::{EditorConfig, EditorMode};
+
+/// Opens the file at the line with the configured editor. Returns the
+/// message for the status bar.
+pub fn open(
+    config: &EditorConfig,
+    file: &str,
+    line: &str,
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+) -> Result<String, String> {
+    // Relative paths are relative to where PMD runs, which isn't where a
+    // tmux pane or a GUI editor starts.
+    let cwd = env::current_dir().map_err(|err| format!("Could not read the current directory: {}", err))?;
+    let path = cwd.join(file);
+    let args = build_args(config, &path.to_string_lossy(), line)?;
+
+    match config.mode {
+        EditorMode::Suspend => {
+            suspend(terminal).map_err(|err| format!("Could not hand the terminal over: {}", err))?;
+
+            let status = Command::new(&args[0]).args(&args[1..]).status();
+
+            restore(terminal).map_err(|err| format!("Could not take the terminal back: {}", err))?;
+
+            match status {
+                Ok(status) if status.success() => Ok(format!("Edited {}:{}", file, line)),
+                Ok(status) => Err(format!("The editor exited with {}", status)),
+                Err(err) => Err(format!("Could not run `{}`: {}", args[0], err)),
+            }
+        }
+        EditorMode::Tmux => {
+            if env::var_os("TMUX").is_none() {
+                return Err("Not running inside tmux, change the editor mode.".to_string());
+            }
+
+            let output = Command::new("tmux")
+                .arg("split-window")
+                .arg("-c")
+                .arg(&cwd)
+                .args(&args)
+                .output()
+                .map_err(|err| format!("Could not run tmux: {}", err))?;
+
+            if output.status.success() {
+                Ok(format!("Opened {}:{} in a tmux pane", file, line))
+            } else {
+                Err(format!("tmux failed: {}", String::from_utf8_lossy(&output.stderr).trim()))
+            }
+        }
+        EditorMode::Background => {
+            let mut child = Command::new(&args[0])
+                .args(&args[1..])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|err| format!("Could not run `{}`: {}", args[0], err))?;
+
+            // Reaps the editor once it exits, nothing waits on it otherwise.
+            thread::spawn(move || child.wait());
+            Ok(format!("Opened {}:{}", file, line))
+        }
+    }
+}
+
+fn suspend(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    terminal.clear()?;
+    terminal.show_cursor()?;
+    disable_raw_mode()
+}
+
+fn restore(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> io::Result<()> {
+    enable_raw_mode()?;
+    // Forces a full redraw over whatever the editor left behind.
+    terminal.clear()
+}
+
+// Splits the template like a shell would before filling it in, so quoted
+// arguments and paths with spaces stay a single argument.
+fn build_args(config: &EditorConfig, file: &str, line: &str) -> Result<Vec<String>, String> {
+    let template = match &config.command {
+        Some(command) => command.clone(),
+        None => {
+            let editor = env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .ok()
+                .filter(|editor| !editor.trim().is_empty())
+                .ok_or("Set $EDITOR or the command in the [editor] config section.")?;
+            format!("{} +{{line}} {{file}}", editor)
+        }
+    };
+
+    // Adapters can't always tell the line, most editors choke on anything
+    // but a positive number.
+    let line = match line.trim().parse::<usize>() {
+        Ok(line) if line > 0 => line.to_string(),
+        _ => "1".to_string(),
+    };
+
+    let mut args: Vec<String> = shell_words::split(&template)
+        .map_err(|err| format!("Could not parse the editor command: {}", err))?
+        .into_iter()
+        .map(|arg| arg.replace("{file}", file).replace("{line}", &line))
+        .collect();
+
+    if args.is_empty() {
+        return Err("The editor command is empty.".to_string());
+    }
+    if !template.contains("{file}") {
+        args.push(file.to_string());
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &str, file: &str, line: &str) -> Result<Vec<String>, String> {
+        let config = EditorConfig {
+            command: Some(command.to_string()),
+            mode: EditorMode::Suspend,
+        };
+
+        build_args(&config, file, line)
+    }
+
+    #[test]
+    fn fills_in_the_template() {
+        assert_eq!(
+            args("code -g {file}:{line}", "/app/my file.rs", "12").unwrap(),
+            vec!["code", "-g", "/app/my file.rs:12"]
+        );
+        assert_eq!(args("subl", "/app/a.rs", "3").unwrap(), vec!["subl", "/app/a.rs"]);
+        // Lines the adapter couldn't tell open the top of the file.
+        assert_eq!(args("vi +{line} {file}", "/a.rs", "?").unwrap(), vec!["vi", "+1", "/a.rs"]);
+    }
+
+    #[test]
+    fn splits_the_template_like_a_shell() {
+        assert_eq!(
+            args("emacsclient -a '' --eval \"(goto-line {line})\" {file}", "/a.rs", "7").unwrap(),
+            vec!["emacsclient", "-a", "", "--eval", "(goto-line 7)", "/a.rs"]
+        );
+        assert!(args("vi 'unterminated", "/a.rs", "1").is_err());
+        assert!(args("  ", "/a.rs", "1").is_err());
+    }
+}
@@ -1,20 +1,26 @@
 use std::{
+    fmt,
     io::Stdout,
-    process,
     time::{Duration, Instant},
 };
 
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{Event, KeyCode};
 use serde::Deserialize;
 use tui::{backend::CrosstermBackend, Terminal};
 
-use crate::ui::{layout::tree, UiState};
+use crate::{
+    server::CommandKind,
+    store::Resume,
+    ui::{layout, UiState},
+};
 
 #[derive(Debug, Deserialize, Clone, Eq, Hash, PartialEq)]
 pub enum InputMode {
     Normal,
     Inspection,
     Visual,
+    Command,
+    Search,
 }
 
 #[derive(Debug, Deserialize, Clone, Eq, Hash, PartialEq)]
@@ -23,24 +29,34 @@ pub enum ListMode {
     Callstack,
 }
 
-impl InputMode {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for InputMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             InputMode::Normal => {
-                String::from("Normal")
+                write!(f, "Normal")
             }
             InputMode::Inspection => {
-                String::from("Inspection")
+                write!(f, "Inspection")
             }
             InputMode::Visual => {
-                String::from("Visual")
+                write!(f, "Visual")
+            }
+            InputMode::Command => {
+                write!(f, "Command")
+            }
+            InputMode::Search => {
+                write!(f, "Search")
             }
         }
     }
 }
 
-pub fn handle_keymap_event(state: &mut UiState, terminal: &mut Terminal<CrosstermBackend<Stdout>>) {
-    if let Event::Key(key) = event::read().unwrap() {
+pub fn handle_keymap_event(
+    event: Event,
+    state: &mut UiState,
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+) {
+    if let Event::Key(key) = event {
         match state.input_mode {
             InputMode::Normal => {
                 normal_keymap(key.code, state, terminal);
@@ -51,6 +67,12 @@ pub fn handle_keymap_event(state: &mut UiState, terminal: &mut Terminal<Crosster
             InputMode::Visual => {
                 visual_keymap(key.code, state, terminal);
             }
+            InputMode::Command => {
+                command_keymap(key.code, state);
+            }
+            InputMode::Search => {
+                search_keymap(key.code, state);
+            }
         }
     }
 }
@@ -62,39 +84,56 @@ pub fn normal_keymap(
 ) {
     let leader_key_timeout = Duration::from_millis(1000);
     let leader_key = KeyCode::Char(state.config.keymap.leader_key);
+    // A chord is only the leader key and the key right after it.
+    let chord = state.leader_pending && leader_key_timeout >= state.leader_tick_time.elapsed();
+    state.leader_pending = false;
 
     match key_code {
         // leader key.
         leader if leader == leader_key => {
             state.leader_tick_time = Instant::now();
+            state.leader_pending = true;
         }
-        KeyCode::Char('l') => {
-            if leader_key_timeout >= state.leader_tick_time.elapsed() {
-                state.list_state.breakpoint.selected = state.list_state.breakpoint.state.selected();
-                state.list_state.callstack.select_first();
-                state.list_mode = ListMode::Callstack;
-            }
+        KeyCode::Char('l') if chord => {
+            state.list_state.breakpoint.selected = state.list_state.breakpoint.state.selected();
+            state.list_state.callstack.select_first();
+            state.list_mode = ListMode::Callstack;
         }
-        KeyCode::Char('h') => {
-            if leader_key_timeout >= state.leader_tick_time.elapsed() {
-                state.list_state.callstack.unselect();
-                state.list_mode = ListMode::Breakpoint;
-                state
-                    .list_state
-                    .breakpoint
-                    .state
-                    .select(state.list_state.breakpoint.selected);
-            }
+        KeyCode::Char('h') if chord => {
+            state.list_state.callstack.unselect();
+            state.list_mode = ListMode::Breakpoint;
+            state
+                .list_state
+                .breakpoint
+                .state
+                .select(state.list_state.breakpoint.selected);
+        }
+        // Stepping, only supported by debugger engines.
+        KeyCode::Char('s') if chord => {
+            resume(state, Resume::StepInto);
+        }
+        KeyCode::Char('n') if chord => {
+            resume(state, Resume::StepOver);
+        }
+        KeyCode::Char('o') if chord => {
+            resume(state, Resume::StepOut);
         }
         KeyCode::Char('q') => {
             terminal.clear().unwrap();
-            process::exit(0);
+            state.quit = true;
         }
+        KeyCode::Char('o') => match layout::selected_location(state) {
+            Some((file, line, _)) => {
+                state.open_in_editor = Some((file, line));
+            }
+            None => {
+                state.status_bar.set_status(String::from("No breakpoint selected."));
+            }
+        },
         KeyCode::Char('j') => match state.list_mode {
             ListMode::Breakpoint => {
                 state.list_state.breakpoint.next();
-                state.tree_state.items =
-                    tree::build_tree_items(state.list_state.breakpoint.get_value());
+                state.refresh_tree();
                 state.list_state.callstack.items = state.list_state.breakpoint.get_callstack();
             }
             ListMode::Callstack => {
@@ -104,8 +143,7 @@ pub fn normal_keymap(
         KeyCode::Down => match state.list_mode {
             ListMode::Breakpoint => {
                 state.list_state.breakpoint.next();
-                state.tree_state.items =
-                    tree::build_tree_items(state.list_state.breakpoint.get_value());
+                state.refresh_tree();
                 state.list_state.callstack.items = state.list_state.breakpoint.get_callstack();
             }
             ListMode::Callstack => {
@@ -115,8 +153,7 @@ pub fn normal_keymap(
         KeyCode::Char('k') => match state.list_mode {
             ListMode::Breakpoint => {
                 state.list_state.breakpoint.previous();
-                state.tree_state.items =
-                    tree::build_tree_items(state.list_state.breakpoint.get_value());
+                state.refresh_tree();
                 state.list_state.callstack.items = state.list_state.breakpoint.get_callstack();
             }
             ListMode::Callstack => {
@@ -126,8 +163,7 @@ pub fn normal_keymap(
         KeyCode::Up => match state.list_mode {
             ListMode::Breakpoint => {
                 state.list_state.breakpoint.previous();
-                state.tree_state.items =
-                    tree::build_tree_items(state.list_state.breakpoint.get_value());
+                state.refresh_tree();
                 state.list_state.callstack.items = state.list_state.breakpoint.get_callstack();
             }
             ListMode::Callstack => {
@@ -138,17 +174,22 @@ pub fn normal_keymap(
             state.popup.show();
             match state.list_mode {
                 ListMode::Breakpoint => {
-                    let breakpoint = state
+                    let stored = state
                         .list_state
                         .breakpoint
                         .get_selected_breakpoint()
                         .unwrap();
+                    let breakpoint = &stored.breakpoint;
 
                     let mut popup_text = vec![];
 
                     let breakpoint_file = format!("[FILE]: {}", breakpoint.filepath);
                     popup_text.push(breakpoint_file);
 
+                    if let Some(original) = &stored.original_filepath {
+                        popup_text.push(format!("[REMOTE FILE]: {}", original));
+                    }
+
                     let breakpoint_line = format!("[LINE]: {}", breakpoint.line);
                     popup_text.push(breakpoint_line);
 
@@ -169,6 +210,10 @@ pub fn normal_keymap(
                     let callstack_file = format!("[FILE]: {}", callstack.filepath);
                     popup_text.push(callstack_file);
 
+                    if let Some(original) = &callstack.original_filepath {
+                        popup_text.push(format!("[REMOTE FILE]: {}", original));
+                    }
+
                     let callstack_line = format!("[LINE]: {}", callstack.line);
                     popup_text.push(callstack_line);
 
@@ -178,18 +223,67 @@ pub fn normal_keymap(
             state.input_mode = InputMode::Visual;
             state
                 .status_bar
-                .set_status(format!(":{}", state.input_mode.to_string()));
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Char('c') => {
+            resume(state, Resume::Continue);
+        }
+        KeyCode::Char('/') => {
+            state.filtered = true;
+            state.refresh_list();
+            state.input_mode = InputMode::Search;
+            state.status_bar.set_status(format!("/{}", state.search));
+        }
+        KeyCode::Char('n') => {
+            jump_to_match(state, true);
+        }
+        KeyCode::Char('N') => {
+            jump_to_match(state, false);
+        }
+        KeyCode::Char(':') => {
+            state.command_line.clear();
+            state.input_mode = InputMode::Command;
+            state.status_bar.set_status(String::from(":"));
+        }
+        KeyCode::Char('r') => {
+            let mut popup_text = vec![format!("[REJECTED]: {}", state.stats.rejected)];
+            if state.stats.rejected > state.rejected.len() as u64 {
+                popup_text.push(format!("Showing the latest {}", state.rejected.len()));
+            }
+
+            // Newest first, so the latest failure is always visible.
+            for rejection in state.rejected.iter().rev() {
+                let path = if rejection.path.is_empty() {
+                    String::from(".")
+                } else {
+                    rejection.path.clone()
+                };
+
+                popup_text.push(format!(
+                    "[{}] {}: {}",
+                    rejection.status, path, rejection.reason
+                ));
+                popup_text.push(format!("      {}", rejection.body));
+            }
+
+            state.popup.set_text(popup_text);
+            state.popup.show();
+            state.input_mode = InputMode::Visual;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Char('p') => {
+            state.raw_payload.toggle();
         }
         KeyCode::Char('i') => {
             state.input_mode = InputMode::Inspection;
             state
                 .status_bar
-                .set_status(format!(":{}", state.input_mode.to_string()));
+                .set_status(format!(":{}", state.input_mode));
         }
-        KeyCode::Esc => {
-            if state.popup.is_active() {
-                state.popup.hide();
-            }
+        KeyCode::Esc if state.popup.is_active() => {
+            state.popup.hide();
         }
         _ => {}
     }
@@ -205,7 +299,7 @@ pub fn inspection_keymap(
             state.input_mode = InputMode::Normal;
             state
                 .status_bar
-                .set_status(format!(":{}", state.input_mode.to_string()));
+                .set_status(format!(":{}", state.input_mode));
         }
         KeyCode::Char('l') => {
             state.tree_state.right();
@@ -219,6 +313,16 @@ pub fn inspection_keymap(
         KeyCode::Right => {
             state.tree_state.left();
         }
+        KeyCode::Char('p') => {
+            state.raw_payload.toggle();
+        }
+        // The raw payload scrolls instead of moving through the tree.
+        KeyCode::Char('j') | KeyCode::Down if state.raw_payload.visible => {
+            state.raw_payload.down();
+        }
+        KeyCode::Char('k') | KeyCode::Up if state.raw_payload.visible => {
+            state.raw_payload.up();
+        }
         KeyCode::Char('j') => {
             state.tree_state.down();
         }
@@ -233,7 +337,7 @@ pub fn inspection_keymap(
         }
         KeyCode::Char('q') => {
             terminal.clear().unwrap();
-            process::exit(0);
+            state.quit = true;
         }
         _ => {}
     }
@@ -244,20 +348,201 @@ pub fn visual_keymap(
     state: &mut UiState,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
 ) {
+    match key_code {
+        KeyCode::Esc if state.popup.is_active() => {
+            state.popup.hide();
+            state.input_mode = InputMode::Normal;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Char('q') => {
+            terminal.clear().unwrap();
+            state.quit = true;
+        }
+        _ => {}
+    }
+}
+
+pub fn command_keymap(key_code: KeyCode, state: &mut UiState) {
     match key_code {
         KeyCode::Esc => {
-            if state.popup.is_active() {
-                state.popup.hide();
-                state.input_mode = InputMode::Normal;
+            state.command_line.clear();
+            state.input_mode = InputMode::Normal;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Enter => {
+            let command_line = std::mem::take(&mut state.command_line);
+            state.input_mode = InputMode::Normal;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+            run_command(command_line.trim(), state);
+        }
+        KeyCode::Backspace => {
+            state.command_line.pop();
+            state
+                .status_bar
+                .set_status(format!(":{}", state.command_line));
+        }
+        KeyCode::Char(c) => {
+            state.command_line.push(c);
+            state
+                .status_bar
+                .set_status(format!(":{}", state.command_line));
+        }
+        _ => {}
+    }
+}
+
+pub fn search_keymap(key_code: KeyCode, state: &mut UiState) {
+    match key_code {
+        // Escape shows every breakpoint again, the search is kept for n/N.
+        KeyCode::Esc => {
+            state.filtered = false;
+            state.refresh_list();
+            state.input_mode = InputMode::Normal;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Enter => {
+            state.input_mode = InputMode::Normal;
+            state
+                .status_bar
+                .set_status(format!(":{}", state.input_mode));
+        }
+        KeyCode::Backspace => {
+            let mut search = state.search.clone();
+            search.pop();
+            state.set_search(search);
+            state.status_bar.set_status(format!("/{}", state.search));
+        }
+        KeyCode::Char(c) => {
+            let search = format!("{}{}", state.search, c);
+            state.set_search(search);
+            state.status_bar.set_status(format!("/{}", state.search));
+        }
+        _ => {}
+    }
+}
+
+// Selects the next or previous breakpoint matching the search, wrapping
+// around. While the list is filtered that's simply the next one.
+fn jump_to_match(state: &mut UiState, forward: bool) {
+    if state.filter.is_empty() {
+        state
+            .status_bar
+            .set_status(String::from("No search, press / to search"));
+        return;
+    }
+
+    let list = &mut state.list_state.breakpoint;
+    let len = list.items.len();
+    let selected = list.state.selected();
+    // Everything after the selection in that direction, the selection last.
+    let position = |offset: usize| match (selected, forward) {
+        (Some(index), true) => (index + offset) % len,
+        (Some(index), false) => (index + len - offset % len) % len,
+        (None, true) => offset - 1,
+        (None, false) => len - offset,
+    };
+    let found = (1..=len)
+        .map(position)
+        .find(|index| state.filter.matches(&list.items[*index]));
+
+    let index = match found {
+        Some(index) => index,
+        None => {
+            state
+                .status_bar
+                .set_status(format!("No matches for /{}", state.search));
+            return;
+        }
+    };
+
+    if state.list_mode == ListMode::Callstack {
+        state.list_state.callstack.unselect();
+        state.list_mode = ListMode::Breakpoint;
+    }
+
+    state.list_state.breakpoint.state.select(Some(index));
+    state.refresh_tree();
+    state.list_state.callstack.items = state.list_state.breakpoint.get_callstack();
+}
+
+// Runs a command typed in command mode against the paused breakpoint.
+fn run_command(command_line: &str, state: &mut UiState) {
+    let (name, args) = match command_line.split_once(' ') {
+        Some((name, args)) => (name, args.trim()),
+        None => (command_line, ""),
+    };
+
+    let kind = match name {
+        "" => return,
+        "c" | "continue" => return resume(state, Resume::Continue),
+        "abort" => return resume(state, Resume::Abort),
+        "e" | "eval" if !args.is_empty() => CommandKind::Eval {
+            expression: args.to_string(),
+        },
+        "set" => match args.split_once(' ') {
+            Some((variable, value)) => CommandKind::Set {
+                variable: variable.to_string(),
+                value: value.trim().to_string(),
+            },
+            None => {
                 state
                     .status_bar
-                    .set_status(format!(":{}", state.input_mode.to_string()));
+                    .set_status(String::from("Usage: set <variable> <value>"));
+                return;
             }
+        },
+        _ => {
+            state
+                .status_bar
+                .set_status(format!("Unknown command: {}", command_line));
+            return;
         }
-        KeyCode::Char('q') => {
-            terminal.clear().unwrap();
-            process::exit(0);
+    };
+
+    let sent = state
+        .target_paused_breakpoint()
+        .and_then(|id| state.store.send_command(id, kind.clone()));
+    match sent {
+        Some(_) => state.status_bar.set_status(format!("Sent: {}", kind)),
+        None => state
+            .status_bar
+            .set_status(String::from("No paused breakpoint")),
+    }
+}
+
+fn resume(state: &mut UiState, resume: Resume) {
+    let target = state.target_paused_breakpoint();
+    let connector_type = target
+        .and_then(|id| state.store.breakpoint(id))
+        .map(|stored| stored.breakpoint.connector_type.clone());
+    if let Some(connector_type) = connector_type.filter(|connector| !resume.supported_by(connector)) {
+        state.status_bar.set_status(format!(
+            "Can't {} a {} breakpoint, stepping needs a DBGp engine",
+            resume.as_str(),
+            connector_type
+        ));
+        return;
+    }
+
+    match target {
+        Some(id) if state.store.resume(id, resume) => {
+            state.paused.remove(&id);
+            state
+                .status_bar
+                .set_status(format!("Resumed breakpoint {}: {}", id, resume.as_str()));
+        }
+        _ => {
+            state
+                .status_bar
+                .set_status(String::from("No paused breakpoint"));
         }
-        _ => {}
     }
 }
@@ -1,19 +1,22 @@
-use std::io::Stdout;
+use std::{io::Stdout, sync::Arc};
 
 use tui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Style},
-    text::Spans,
+    text::{Span, Spans},
     widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
     Frame,
 };
 
+mod highlight;
+pub mod payload;
+pub mod source;
 pub mod tree;
 
 use crate::{
-    server::{Breakpoint, Callstack},
-    ui::UiState,
+    store::{self, Stored},
+    ui::{keymap::ListMode, UiState},
 };
 
 // @todo: Use traits for this.
@@ -21,24 +24,60 @@ use crate::{
 pub struct BreakpointList {
     pub state: ListState,
     pub selected: Option<usize>,
-    pub items: Vec<Breakpoint>,
+    pub items: Vec<Arc<Stored>>,
+    // Id of the selected breakpoint while the list is empty, e.g. while a
+    // search has no matches, so it's selected again once it has.
+    hidden_selection: Option<u64>,
 }
 
 #[derive(Debug)]
 pub struct CallstackList {
     pub state: ListState,
-    pub items: Vec<Callstack>,
+    pub items: Vec<store::Frame>,
 }
 
 impl BreakpointList {
-    pub fn with_items(items: Vec<Breakpoint>) -> BreakpointList {
+    pub fn with_items(items: Vec<Arc<Stored>>) -> BreakpointList {
         BreakpointList {
             state: ListState::default(),
             selected: None,
             items,
+            hidden_selection: None,
         }
     }
 
+    /// Replaces the items, keeping the same breakpoint selected when it's
+    /// still there, otherwise the next one. Returns whether the selected
+    /// breakpoint changed.
+    pub fn set_items(&mut self, items: Vec<Arc<Stored>>) -> bool {
+        let visible_id = self.id_at(self.state.selected());
+        let selected_id = visible_id.or(self.hidden_selection.take());
+        let saved_id = self.id_at(self.selected);
+
+        self.items = items;
+        if self.items.is_empty() {
+            self.hidden_selection = selected_id;
+        }
+        let selected = self.position_of(selected_id);
+        self.state.select(selected);
+        self.selected = self.position_of(saved_id);
+
+        self.id_at(selected) != visible_id
+    }
+
+    fn id_at(&self, index: Option<usize>) -> Option<u64> {
+        index.and_then(|i| self.items.get(i)).map(|breakpoint| breakpoint.id)
+    }
+
+    // Ids are increasing, so a removed breakpoint is replaced by the next
+    // one, or the last one when it was at the end.
+    fn position_of(&self, id: Option<u64>) -> Option<usize> {
+        let id = id?;
+        let next = self.items.partition_point(|breakpoint| breakpoint.id < id);
+        Some(next.min(self.items.len().checked_sub(1)?))
+    }
+
+    #[allow(dead_code)]
     pub fn get_selected_filepath(&mut self) -> String {
         if self.items.is_empty() {
             return "No data".to_string();
@@ -46,7 +85,7 @@ impl BreakpointList {
 
         let selected = self.state.selected().unwrap();
         let data = &self.items[selected];
-        data.filepath.clone()
+        data.breakpoint.filepath.clone()
     }
 
     pub fn next(&mut self) {
@@ -85,27 +124,16 @@ impl BreakpointList {
         self.state.select(Some(i));
     }
 
-    pub fn get_selected_breakpoint(&mut self) -> Option<&Breakpoint> {
+    pub fn get_selected_breakpoint(&mut self) -> Option<&Stored> {
         if self.items.is_empty() {
             return None;
         }
 
-        let selected = self.state.selected().unwrap();
-        let breakpoint = &self.items[selected];
-        Some(breakpoint)
-    }
-
-    pub fn get_value(&mut self) -> String {
-        if self.items.is_empty() {
-            return "".to_string();
-        }
-
-        let selected = self.state.selected().unwrap();
-        let data = &self.items[selected];
-        data.payload.clone()
+        let selected = self.state.selected()?;
+        self.items.get(selected).map(|breakpoint| breakpoint.as_ref())
     }
 
-    pub fn get_callstack(&mut self) -> Vec<Callstack> {
+    pub fn get_callstack(&mut self) -> Vec<store::Frame> {
         if self.items.is_empty() {
             return vec![];
         }
@@ -113,8 +141,7 @@ impl BreakpointList {
         let selected = self.state.selected();
         match selected {
             Some(selected_i) => {
-                let data = &self.items[selected_i];
-                data.callstack.to_owned()
+                self.items[selected_i].frames()
             }
             None => {
                 vec![]
@@ -122,27 +149,27 @@ impl BreakpointList {
         }
     }
 
+    #[allow(dead_code)]
     pub fn unselect(&mut self) {
         self.state.select(None);
     }
 }
 
 impl CallstackList {
-    pub fn with_items(items: Vec<Callstack>) -> CallstackList {
+    pub fn with_items(items: Vec<store::Frame>) -> CallstackList {
         CallstackList {
             state: ListState::default(),
             items,
         }
     }
 
-    pub fn get_selected_callstack(&mut self) -> Option<&Callstack> {
+    pub fn get_selected_callstack(&mut self) -> Option<&store::Frame> {
         if self.items.is_empty() {
             return None;
         }
 
-        let selected = self.state.selected().unwrap();
-        let callstack = &self.items[selected];
-        Some(callstack)
+        let selected = self.state.selected()?;
+        self.items.get(selected)
     }
 
     pub fn next(&mut self) {
@@ -185,6 +212,7 @@ impl CallstackList {
         self.state.select(None);
     }
 
+    #[allow(dead_code)]
     pub fn get_selected_filepath(&mut self) -> String {
         if self.items.is_empty() {
             return "No data".to_string();
@@ -226,12 +254,12 @@ pub fn render_main(state: &mut UiState, f: &mut Frame<CrosstermBackend<Stdout>>)
 
     let bottom_layout = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(100)].as_ref())
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(main[1]);
 
     let status_layout = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(100)].as_ref())
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
         .split(main[2]);
 
     let breakpoints_layout = Block::default().title("Breakpoints").borders(Borders::ALL);
@@ -243,9 +271,15 @@ pub fn render_main(state: &mut UiState, f: &mut Frame<CrosstermBackend<Stdout>>)
         .items
         .iter()
         .map(|i| {
-            let filepath = &i.filepath;
-            let line_num = &i.line;
+            let filepath = i.display_filepath();
+            let line_num = &i.breakpoint.line;
             let title = format!("[{}] {}", line_num, filepath);
+
+            if state.paused.contains(&i.id) {
+                let lines = vec![Spans::from(format!("[PAUSED] {}", title))];
+                return ListItem::new(lines).style(Style::default().fg(Color::Yellow));
+            }
+
             let lines = vec![Spans::from(title)];
             ListItem::new(lines).style(Style::default().fg(Color::White))
         })
@@ -271,7 +305,7 @@ pub fn render_main(state: &mut UiState, f: &mut Frame<CrosstermBackend<Stdout>>)
         .items
         .iter()
         .map(|i| {
-            let filepath = &i.filepath;
+            let filepath = i.display_filepath();
             let line_num = &i.line;
             let title = format!("[{}] {}", line_num, filepath);
             let lines = vec![Spans::from(title)];
@@ -290,13 +324,31 @@ pub fn render_main(state: &mut UiState, f: &mut Frame<CrosstermBackend<Stdout>>)
         &mut state.list_state.callstack.state,
     );
 
-    let items = tree::render_tree(state.tree_state.items.to_vec());
-    f.render_stateful_widget(items, bottom_layout[0], &mut state.tree_state.state);
+    if state.raw_payload.visible {
+        let breakpoint = state.list_state.breakpoint.get_selected_breakpoint();
+        let payload = payload::render_raw_payload(&mut state.raw_payload, breakpoint, bottom_layout[0]);
+        f.render_widget(payload, bottom_layout[0]);
+    } else {
+        let items = tree::render_tree(state.tree_state.items.to_vec());
+        f.render_stateful_widget(items, bottom_layout[0], &mut state.tree_state.state);
+    }
+
+    let location = selected_location(state);
+    let location = location
+        .as_ref()
+        .map(|(path, line, connector_type)| (path.as_str(), line.as_str(), connector_type.as_str()));
+    let source = source::render_source(&mut state.source, location, bottom_layout[1]);
+    f.render_widget(source, bottom_layout[1]);
 
     let status_bar = Paragraph::new(state.status_bar.get_status())
         .block(Block::default().borders(Borders::NONE));
     f.render_widget(status_bar, status_layout[0]);
 
+    let stats_bar = Paragraph::new(render_stats(state))
+        .alignment(Alignment::Right)
+        .block(Block::default().borders(Borders::NONE));
+    f.render_widget(stats_bar, status_layout[1]);
+
     // popup element
     if state.popup.is_active() {
         let area = render_popup(100, 20, size);
@@ -315,6 +367,77 @@ pub fn render_main(state: &mut UiState, f: &mut Frame<CrosstermBackend<Stdout>>)
     }
 }
 
+// The selected callstack frame when browsing the callstack, otherwise the
+// selected breakpoint, along with the connector that sent it.
+pub fn selected_location(state: &mut UiState) -> Option<(String, String, String)> {
+    let connector_type = state
+        .list_state
+        .breakpoint
+        .get_selected_breakpoint()
+        .map(|stored| stored.breakpoint.connector_type.clone())
+        .unwrap_or_default();
+
+    if state.list_mode == ListMode::Callstack {
+        if let Some(frame) = state.list_state.callstack.get_selected_callstack() {
+            return Some((frame.filepath.clone(), frame.line.clone(), connector_type));
+        }
+    }
+
+    state
+        .list_state
+        .breakpoint
+        .get_selected_breakpoint()
+        .map(|stored| {
+            let breakpoint = &stored.breakpoint;
+            (breakpoint.filepath.clone(), breakpoint.line.clone(), connector_type)
+        })
+}
+
+// Summary of what the server received, shown on the right of the status bar.
+fn render_stats(state: &UiState) -> Spans<'static> {
+    let stats = state.stats;
+    let mut spans = vec![];
+
+    // The search stays after leaving search mode, dimmed once the list
+    // isn't filtered by it anymore.
+    if !state.filter.is_empty() {
+        let color = if state.filtered { Color::Cyan } else { Color::DarkGray };
+        spans.push(Span::styled(
+            format!("/{}", state.search.trim()),
+            Style::default().fg(color),
+        ));
+        spans.push(Span::raw(format!(" {} matching | ", state.matching)));
+    }
+
+    spans.push(Span::raw(format!("{} dumps", stats.received)));
+
+    if stats.shed > 0 {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(
+            format!("{} shed", stats.shed),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    if stats.rejected > 0 {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(
+            format!("{} rejected", stats.rejected),
+            Style::default().fg(Color::Red),
+        ));
+    }
+
+    if stats.unauthorized > 0 {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(
+            format!("{} unauthorized", stats.unauthorized),
+            Style::default().fg(Color::Red),
+        ));
+    }
+
+    Spans::from(spans)
+}
+
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 pub fn render_popup(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -341,3 +464,76 @@ pub fn render_popup(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         )
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::server::Breakpoint;
+
+    fn items(ids: &[u64]) -> Vec<Arc<Stored>> {
+        ids.iter()
+            .map(|id| {
+                let breakpoint = Breakpoint::new("src/main.rs", id.to_string(), "rust", Value::Null);
+                Arc::new(Stored::new(*id, breakpoint, None))
+            })
+            .collect()
+    }
+
+    fn selected_id(list: &mut BreakpointList) -> Option<u64> {
+        list.get_selected_breakpoint().map(|breakpoint| breakpoint.id)
+    }
+
+    #[test]
+    fn keeps_the_selected_breakpoint() {
+        let mut list = BreakpointList::with_items(items(&[1, 2, 3, 4]));
+        list.state.select(Some(2));
+
+        assert!(!list.set_items(items(&[3, 4])));
+        assert_eq!(selected_id(&mut list), Some(3));
+
+        assert!(!list.set_items(items(&[1, 2, 3, 4, 5])));
+        assert_eq!(selected_id(&mut list), Some(3));
+    }
+
+    #[test]
+    fn selects_the_next_breakpoint_when_the_selected_one_is_removed() {
+        let mut list = BreakpointList::with_items(items(&[1, 2, 3, 4]));
+        list.state.select(Some(1));
+
+        assert!(list.set_items(items(&[1, 3, 4])));
+        assert_eq!(selected_id(&mut list), Some(3));
+
+        // Past the end the last one is selected.
+        list.state.select(Some(2));
+        assert!(list.set_items(items(&[1, 3])));
+        assert_eq!(selected_id(&mut list), Some(3));
+    }
+
+    #[test]
+    fn restores_the_selection_after_an_empty_result() {
+        let mut list = BreakpointList::with_items(items(&[1, 2, 3]));
+        list.state.select(Some(1));
+
+        assert!(list.set_items(vec![]));
+        assert_eq!(list.state.selected(), None);
+        assert_eq!(list.hidden_selection, Some(2));
+
+        // Still empty, nothing changed.
+        assert!(!list.set_items(vec![]));
+        assert_eq!(list.hidden_selection, Some(2));
+
+        assert!(list.set_items(items(&[1, 2, 3])));
+        assert_eq!(selected_id(&mut list), Some(2));
+        assert_eq!(list.hidden_selection, None);
+    }
+
+    #[test]
+    fn keeps_nothing_selected() {
+        let mut list = BreakpointList::with_items(items(&[1, 2]));
+
+        assert!(!list.set_items(items(&[1, 2, 3])));
+        assert_eq!(list.state.selected(), None);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{fmt, ops::Range, path::Path, sync::OnceLock};
+
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{FontStyle, Style as SyntectStyle, Theme, ThemeSet},
+    parsing::{SyntaxReference, SyntaxSet},
+};
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+// Highlighting has to start from the top of the file, so lines past this
+// are shown as plain text instead of stalling the UI.
+const MAX_HIGHLIGHTED_LINES: usize = 5_000;
+
+const THEME: &str = "base16-ocean.dark";
+
+/// The syntaxes and the theme embedded in the binary, loaded on first use.
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+fn highlighter() -> &'static Highlighter {
+    static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+
+    HIGHLIGHTER.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove(THEME).expect("The default theme is missing."),
+        }
+    })
+}
+
+/// Lines that are highlighted as they are shown. The state of the syntax
+/// is kept at the last highlighted line, so scrolling down continues from
+/// there and every line is only highlighted once.
+pub struct Highlighted {
+    lines: Vec<String>,
+    // None without a syntax, the lines are then shown as they are.
+    state: Option<HighlightLines<'static>>,
+    // The lines highlighted so far, from the top.
+    spans: Vec<Spans<'static>>,
+}
+
+impl Highlighted {
+    /// Picks the syntax by the file extension, falling back to the
+    /// connector that sent the breakpoint.
+    pub fn source(lines: Vec<String>, path: &str, connector_type: &str) -> Highlighted {
+        let highlighter = highlighter();
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        let syntax = highlighter
+            .syntaxes
+            .find_syntax_by_extension(extension)
+            .or_else(|| {
+                connector_extension(connector_type)
+                    .and_then(|extension| highlighter.syntaxes.find_syntax_by_extension(extension))
+            });
+
+        Highlighted::new(lines, syntax)
+    }
+
+    /// Pretty printed JSON.
+    pub fn json(text: &str) -> Highlighted {
+        let lines = text.lines().map(String::from).collect();
+        let syntax = highlighter()
+            .syntaxes
+            .find_syntax_by_extension("json")
+            .expect("The default JSON syntax is missing.");
+
+        Highlighted::new(lines, Some(syntax))
+    }
+
+    /// Lines that are shown as they are.
+    pub fn plain(lines: Vec<String>) -> Highlighted {
+        Highlighted::new(lines, None)
+    }
+
+    fn new(lines: Vec<String>, syntax: Option<&'static SyntaxReference>) -> Highlighted {
+        let state = syntax.map(|syntax| HighlightLines::new(syntax, &highlighter().theme));
+
+        Highlighted {
+            lines,
+            state,
+            spans: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Returns the lines in the range, highlighting the ones not seen yet.
+    pub fn window(&mut self, range: Range<usize>) -> Vec<Spans<'static>> {
+        let end = range.end.min(self.lines.len());
+        let start = range.start.min(end);
+
+        if let Some(state) = &mut self.state {
+            let highlight_to = end.min(MAX_HIGHLIGHTED_LINES);
+            for line in &self.lines[self.spans.len().min(highlight_to)..highlight_to] {
+                self.spans.push(highlight_line(state, line));
+            }
+        }
+
+        (start..end)
+            .map(|index| match self.spans.get(index) {
+                Some(spans) => spans.clone(),
+                None => Spans::from(self.lines[index].clone()),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Highlighted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Highlighted")
+            .field("lines", &self.lines.len())
+            .field("highlighted", &self.spans.len())
+            .finish()
+    }
+}
+
+// Languages of the known adapters, for files without a usable extension.
+fn connector_extension(connector_type: &str) -> Option<&'static str> {
+    match connector_type {
+        "php" | "dbgp" => Some("php"),
+        "rust" | "tracing" | "log" => Some("rs"),
+        "js" | "javascript" | "node" => Some("js"),
+        "python" => Some("py"),
+        "ruby" => Some("rb"),
+        "go" => Some("go"),
+        _ => None,
+    }
+}
+
+fn highlight_line(state: &mut HighlightLines<'static>, line: &str) -> Spans<'static> {
+    let highlighter = highlighter();
+
+    // The newline keeps the syntax state right for the next line.
+    match state.highlight_line(&format!("{}\n", line), &highlighter.syntaxes) {
+        Ok(regions) => Spans::from(
+            regions
+                .into_iter()
+                .map(|(style, text)| Span::styled(text.trim_end_matches('\n').to_string(), to_style(style)))
+                .collect::<Vec<Span>>(),
+        ),
+        Err(_) => Spans::from(line.to_string()),
+    }
+}
+
+// Only the foreground is taken over, the background stays the terminal's.
+fn to_style(style: SyntectStyle) -> Style {
+    let mut converted = Style::default().fg(Color::Rgb(
+        style.foreground.r,
+        style.foreground.g,
+        style.foreground.b,
+    ));
+
+    if style.font_style.contains(FontStyle::BOLD) {
+        converted = converted.add_modifier(Modifier::BOLD);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        converted = converted.add_modifier(Modifier::ITALIC);
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        converted = converted.add_modifier(Modifier::UNDERLINED);
+    }
+
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_only_up_to_the_window() {
+        let lines = (0..100).map(|index| format!("let a{} = {};", index, index)).collect();
+        let mut highlighted = Highlighted::source(lines, "main.rs", "rust");
+
+        let window = highlighted.window(10..20);
+        assert_eq!(window.len(), 10);
+        assert_eq!(highlighted.spans.len(), 20);
+        assert!(window[0].0.len() > 1);
+
+        // Past the end only returns what's there.
+        assert_eq!(highlighted.window(95..120).len(), 5);
+        assert_eq!(highlighted.spans.len(), 100);
+    }
+
+    #[test]
+    fn shows_plain_lines_past_the_cap() {
+        let lines = vec!["{\"a\": 1}".to_string(); MAX_HIGHLIGHTED_LINES + 10];
+        let mut highlighted = Highlighted::json(&lines.join("\n"));
+
+        let window = highlighted.window(MAX_HIGHLIGHTED_LINES - 1..MAX_HIGHLIGHTED_LINES + 1);
+        assert!(window[0].0.len() > 1);
+        assert_eq!(window[1].0.len(), 1);
+        assert_eq!(highlighted.spans.len(), MAX_HIGHLIGHTED_LINES);
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json::Value;
+use tui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::store::Stored;
+
+use super::highlight::Highlighted;
+
+/// The pretty printed payload of the selected breakpoint, shown instead of
+/// the inspection tree when toggled on.
+#[derive(Debug)]
+pub struct RawPayload {
+    pub visible: bool,
+    // Lines scrolled past, reset when another breakpoint is selected.
+    scroll: u16,
+    // Id of the breakpoint the lines are for.
+    id: Option<u64>,
+    lines: Highlighted,
+}
+
+impl RawPayload {
+    pub fn new() -> RawPayload {
+        RawPayload {
+            visible: false,
+            scroll: 0,
+            id: None,
+            lines: Highlighted::plain(vec![]),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn down(&mut self) {
+        if (self.scroll as usize) + 1 < self.lines.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    fn load(&mut self, breakpoint: &Stored) {
+        if self.id == Some(breakpoint.id) {
+            return;
+        }
+
+        self.id = Some(breakpoint.id);
+        self.scroll = 0;
+        self.lines = match (&breakpoint.payload_error, &breakpoint.breakpoint.payload) {
+            // Broken JSON can't be highlighted, show it as it was sent.
+            (Some(_), Value::String(raw)) => Highlighted::plain(raw.lines().map(String::from).collect()),
+            (_, payload) => {
+                let text = serde_json::to_string_pretty(payload).expect("Could not serialize the payload.");
+                Highlighted::json(&text)
+            }
+        };
+    }
+}
+
+pub fn render_raw_payload(payload: &mut RawPayload, breakpoint: Option<&Stored>, area: Rect) -> Paragraph<'static> {
+    let block = Block::default().borders(Borders::ALL).title("Payload (raw)");
+
+    let breakpoint = match breakpoint {
+        Some(breakpoint) => breakpoint,
+        None => {
+            return Paragraph::new(Span::styled(
+                "No breakpoint selected.",
+                Style::default().fg(Color::DarkGray),
+            ))
+            .block(block)
+        }
+    };
+
+    payload.load(breakpoint);
+    // Only the shown lines are highlighted, payloads can be huge.
+    let start = payload.scroll as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    Paragraph::new(payload.lines.window(start..start + height)).block(block)
+}
@@ -0,0 +1,119 @@
+use std::{fs, time::SystemTime};
+
+use tui::{
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Paragraph},
+};
+
+use super::highlight::Highlighted;
+
+// Files larger than this aren't previewed, they are most likely not source code.
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+
+/// The file shown in the source pane, read again only when another file is
+/// selected or the file changed, and highlighted as far as it's shown.
+#[derive(Debug)]
+pub struct SourcePreview {
+    path: Option<String>,
+    // Modification time of the file when it was read.
+    modified: Option<SystemTime>,
+    lines: Result<Highlighted, String>,
+}
+
+impl SourcePreview {
+    pub fn new() -> SourcePreview {
+        SourcePreview {
+            path: None,
+            modified: None,
+            lines: Ok(Highlighted::plain(vec![])),
+        }
+    }
+
+    fn load(&mut self, path: &str, connector_type: &str) {
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if self.path.as_deref() == Some(path) && self.modified == modified {
+            return;
+        }
+
+        self.path = Some(path.to_string());
+        self.modified = modified;
+        self.lines = read_lines(path)
+            .map(|lines| Highlighted::source(lines, path, connector_type));
+    }
+}
+
+fn read_lines(path: &str) -> Result<Vec<String>, String> {
+    let metadata = fs::metadata(path).map_err(|err| err.to_string())?;
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err("The file is too large to preview.".to_string());
+    }
+
+    let bytes = fs::read(path).map_err(|err| err.to_string())?;
+    let text = String::from_utf8_lossy(&bytes);
+
+    // Tabs aren't expanded by the terminal backend.
+    Ok(text.lines().map(|line| line.replace('\t', "    ")).collect())
+}
+
+/// Renders the lines around `line` of the file, the line itself highlighted.
+/// The connector picks the syntax for files without a known extension.
+pub fn render_source(preview: &mut SourcePreview, location: Option<(&str, &str, &str)>, area: Rect) -> Paragraph<'static> {
+    let (path, line, connector_type) = match location {
+        Some(location) => location,
+        None => return placeholder("Source", "No breakpoint selected.".to_string()),
+    };
+
+    preview.load(path, connector_type);
+    let title = format!("Source: {}:{}", path, line);
+    let lines = match &mut preview.lines {
+        Ok(lines) => lines,
+        Err(err) => return placeholder(&title, format!("Can't read {}: {}", path, err)),
+    };
+
+    // Line numbers start at 1, anything else only shows the top of the file.
+    let current = line.trim().parse::<usize>().ok().filter(|line| *line > 0);
+    let height = area.height.saturating_sub(2) as usize;
+    let start = current
+        .map(|current| (current - 1).saturating_sub(height / 2))
+        .unwrap_or(0)
+        .min(lines.len().saturating_sub(height));
+    let gutter = (start + height).min(lines.len()).to_string().len();
+
+    let text: Vec<Spans> = lines
+        .window(start..start + height)
+        .into_iter()
+        .enumerate()
+        .map(|(offset, text)| {
+            let index = start + offset;
+            let number = index + 1;
+            let gutter = format!("{:>width$} ", number, width = gutter);
+
+            if Some(number) == current {
+                // Keeps the syntax colors, only the background marks the line.
+                let style = Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD);
+                let mut spans = vec![Span::styled(
+                    gutter,
+                    Style::default().fg(Color::Black).bg(Color::Yellow),
+                )];
+                spans.extend(text.0.iter().map(|span| {
+                    Span::styled(span.content.clone(), span.style.patch(style))
+                }));
+                Spans::from(spans)
+            } else {
+                let mut spans = vec![Span::styled(gutter, Style::default().fg(Color::DarkGray))];
+                spans.extend(text.0.iter().cloned());
+                Spans::from(spans)
+            }
+        })
+        .collect();
+
+    Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(title))
+}
+
+fn placeholder(title: &str, text: String) -> Paragraph<'static> {
+    Paragraph::new(Span::styled(text, Style::default().fg(Color::DarkGray)))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title(title.to_string()))
+}
@@ -1,10 +1,13 @@
 use serde_json::Value;
 use tui::{
     style::{Color, Modifier, Style},
+    text::{Span, Spans},
     widgets::{Block, Borders},
 };
 use tui_tree_widget::{Tree, TreeItem};
 
+use crate::{server::Reply, store::Stored};
+
 pub fn render_tree(items: Vec<TreeItem>) -> Tree {
     let items = Tree::new(items)
         .block(
@@ -23,60 +26,106 @@ pub fn render_tree(items: Vec<TreeItem>) -> Tree {
     items
 }
 
-pub fn build_tree_items(payload: String) -> Vec<TreeItem<'static>> {
-    let mut items = vec![];
+pub fn build_tree_items(breakpoint: Option<&Stored>, replies: &[Reply]) -> Vec<TreeItem<'static>> {
+    let breakpoint = match breakpoint {
+        Some(breakpoint) => breakpoint,
+        None => return vec![],
+    };
 
-    if payload.is_empty() || payload == "No variables" {
-        return items;
+    let mut items = build_payload_items(breakpoint);
+    for reply in replies {
+        items.push(build_reply_item(reply));
     }
 
-    let data: Value = serde_json::from_str(&payload).unwrap();
-    if data.is_string() {
-        items.push(TreeItem::new_leaf(data.to_string()));
+    items
+}
+
+// Replies to commands sent from the UI are shown after the payload.
+fn build_reply_item(reply: &Reply) -> TreeItem<'static> {
+    let key = format!("» {}", reply.command.kind);
+
+    match &reply.error {
+        Some(error) => TreeItem::new_leaf(Spans::from(vec![
+            Span::raw(key),
+            Span::styled(" ! ", Style::default().fg(Color::DarkGray)),
+            Span::styled(error.clone(), Style::default().fg(Color::Red)),
+        ])),
+        None => build_tree_item(key, &reply.result),
     }
+}
 
-    if data.is_object() {
-        let obj = data.as_object().unwrap();
-        for item in obj {
-            let key = item.0;
-            let value = item.1;
-
-            // Strings and numbers.
-            if value.is_string() || value.is_number() {
-                items.push(TreeItem::new_leaf(value.to_string()))
-            }
-
-            if value.is_object() {
-                let leaf = TreeItem::new_leaf(key.to_string());
-                let recursive_tree = recursive_flatten(value, &mut leaf.clone());
-                items.push(recursive_tree);
-            }
-        }
+fn build_payload_items(stored: &Stored) -> Vec<TreeItem<'static>> {
+    let payload = &stored.breakpoint.payload;
+    if let Some(error) = &stored.payload_error {
+        let label = Spans::from(Span::styled(
+            format!("Invalid payload: {}", error),
+            Style::default().fg(Color::Red),
+        ));
+        let raw = payload.as_str().unwrap_or_default().to_string();
+        return vec![TreeItem::new(label, vec![TreeItem::new_leaf(raw)])];
     }
 
-    items
+    match payload {
+        Value::Null => vec![],
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(key, value)| build_tree_item(key.to_string(), value))
+            .collect(),
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(index, value)| build_tree_item(format!("[{}]", index), value))
+            .collect(),
+        scalar => vec![TreeItem::new_leaf(Spans::from(scalar_span(scalar)))],
+    }
 }
 
-// Recursive flatten an object, so we can extract only strings and numbers.
-fn recursive_flatten<'a>(value: &Value, result: &mut TreeItem<'a>) -> TreeItem<'a> {
-    let obj = value.as_object().unwrap();
+// Maps a single value onto a tree item, objects and arrays become
+// expandable nodes while everything else is a leaf.
+fn build_tree_item(key: String, value: &Value) -> TreeItem<'static> {
+    match value {
+        Value::Object(obj) => {
+            let children: Vec<TreeItem> = obj
+                .iter()
+                .map(|(child_key, child_value)| build_tree_item(child_key.to_string(), child_value))
+                .collect();
 
-    for item in obj {
-        let key = item.0;
-        let value = item.1;
-
-        // Strings and numbers.
-        if value.is_string() || value.is_number() {
-            result.add_child(TreeItem::new(
-                key.to_string(),
-                vec![TreeItem::new_leaf(value.to_string())],
-            ));
+            TreeItem::new(node_label(key, "object", obj.len()), children)
         }
+        Value::Array(arr) => {
+            let children: Vec<TreeItem> = arr
+                .iter()
+                .enumerate()
+                .map(|(index, child_value)| build_tree_item(format!("[{}]", index), child_value))
+                .collect();
 
-        if value.is_object() {
-            recursive_flatten(value, result);
+            TreeItem::new(node_label(key, "array", arr.len()), children)
         }
+        scalar => TreeItem::new_leaf(Spans::from(vec![
+            Span::raw(key),
+            Span::styled(" = ", Style::default().fg(Color::DarkGray)),
+            scalar_span(scalar),
+        ])),
     }
+}
+
+fn node_label(key: String, kind: &'static str, len: usize) -> Spans<'static> {
+    Spans::from(vec![
+        Span::raw(key),
+        Span::styled(
+            format!(": {} ({})", kind, len),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ])
+}
+
+fn scalar_span(value: &Value) -> Span<'static> {
+    let color = match value {
+        Value::String(_) => Color::Green,
+        Value::Number(_) => Color::Cyan,
+        Value::Bool(_) => Color::Yellow,
+        _ => Color::DarkGray,
+    };
 
-    result.clone()
+    Span::styled(value.to_string(), Style::default().fg(color))
 }
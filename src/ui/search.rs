@@ -0,0 +1,262 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{server::Breakpoint, store::Stored};
+
+/// A query typed in search mode, a breakpoint has to match every term.
+///
+/// `file:` and `conn:` match the filepath and the connector type, `line:`
+/// takes a number optionally prefixed with `>`, `>=`, `<` or `<=`. Any
+/// other term is searched in the filepath, connector type, line and
+/// payload. Everything is case insensitive.
+#[derive(Debug, Default)]
+pub struct Filter {
+    terms: Vec<Term>,
+    // Searched text and result per breakpoint id, so neither typing nor
+    // new breakpoints arriving search the older payloads again.
+    cache: HashMap<u64, Searched>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Term {
+    File(String),
+    Connector(String),
+    Line(Vec<Ordering>, u64),
+    Text(String),
+}
+
+/// The lowercased text of a breakpoint.
+#[derive(Debug)]
+struct Searched {
+    filepath: String,
+    original_filepath: Option<String>,
+    connector_type: String,
+    // Only serialized when a term needs it, it's the slow part.
+    payload: Option<String>,
+    // Whether it matches the current terms, reset when they change.
+    matches: Option<bool>,
+}
+
+impl Filter {
+    /// Replaces the terms, the text of the breakpoints is kept.
+    pub fn set_query(&mut self, query: &str) {
+        self.terms = query
+            .split_whitespace()
+            .map(|term| parse_term(&term.to_lowercase()))
+            .collect();
+
+        for searched in self.cache.values_mut() {
+            searched.matches = None;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&mut self, breakpoint: &Stored) -> bool {
+        if self.terms.is_empty() {
+            return true;
+        }
+
+        let searched = self
+            .cache
+            .entry(breakpoint.id)
+            .or_insert_with(|| Searched::new(breakpoint));
+        if let Some(matches) = searched.matches {
+            return matches;
+        }
+
+        let matches = self
+            .terms
+            .iter()
+            .all(|term| term.matches(&breakpoint.breakpoint, searched));
+        searched.matches = Some(matches);
+
+        matches
+    }
+
+    /// Forgets the breakpoints that are no longer stored.
+    pub fn retain(&mut self, breakpoints: &[Arc<Stored>]) {
+        if self.cache.len() <= breakpoints.len() {
+            return;
+        }
+
+        let ids: HashSet<u64> = breakpoints.iter().map(|breakpoint| breakpoint.id).collect();
+        self.cache.retain(|id, _| ids.contains(id));
+    }
+}
+
+impl Term {
+    fn matches(&self, breakpoint: &Breakpoint, searched: &mut Searched) -> bool {
+        match self {
+            Term::File(text) => {
+                searched.filepath.contains(text.as_str())
+                    || searched
+                        .original_filepath
+                        .as_deref()
+                        .is_some_and(|original| original.contains(text.as_str()))
+            }
+            Term::Connector(text) => searched.connector_type.contains(text.as_str()),
+            Term::Line(orderings, line) => breakpoint
+                .line
+                .trim()
+                .parse::<u64>()
+                .is_ok_and(|value| orderings.contains(&value.cmp(line))),
+            Term::Text(text) => {
+                searched.display_filepath().contains(text.as_str())
+                    || searched.connector_type.contains(text.as_str())
+                    || breakpoint.line.contains(text.as_str())
+                    || searched
+                        .payload
+                        .get_or_insert_with(|| breakpoint.payload.to_string().to_lowercase())
+                        .contains(text.as_str())
+            }
+        }
+    }
+}
+
+impl Searched {
+    fn new(stored: &Stored) -> Searched {
+        Searched {
+            filepath: stored.breakpoint.filepath.to_lowercase(),
+            original_filepath: stored.original_filepath.as_deref().map(str::to_lowercase),
+            connector_type: stored.breakpoint.connector_type.to_lowercase(),
+            payload: None,
+            matches: None,
+        }
+    }
+
+    fn display_filepath(&self) -> &str {
+        self.original_filepath.as_deref().unwrap_or(&self.filepath)
+    }
+}
+
+// Terms that don't parse are searched as text, so typing `line:` doesn't
+// hide everything before the number is there.
+fn parse_term(term: &str) -> Term {
+    if let Some(text) = term.strip_prefix("file:").filter(|text| !text.is_empty()) {
+        return Term::File(text.to_string());
+    }
+    if let Some(text) = term.strip_prefix("conn:").filter(|text| !text.is_empty()) {
+        return Term::Connector(text.to_string());
+    }
+    if let Some(line) = term.strip_prefix("line:").and_then(parse_line) {
+        return line;
+    }
+
+    Term::Text(term.to_string())
+}
+
+fn parse_line(value: &str) -> Option<Term> {
+    let (orderings, number) = if let Some(number) = value.strip_prefix(">=") {
+        (vec![Ordering::Greater, Ordering::Equal], number)
+    } else if let Some(number) = value.strip_prefix("<=") {
+        (vec![Ordering::Less, Ordering::Equal], number)
+    } else if let Some(number) = value.strip_prefix('>') {
+        (vec![Ordering::Greater], number)
+    } else if let Some(number) = value.strip_prefix('<') {
+        (vec![Ordering::Less], number)
+    } else {
+        (vec![Ordering::Equal], value)
+    };
+
+    number.parse().ok().map(|number| Term::Line(orderings, number))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn breakpoint(id: u64, filepath: &str, line: &str, connector_type: &str) -> Stored {
+        let breakpoint = Breakpoint::new(filepath, line, connector_type, json!({"user": "Alice"}));
+
+        Stored::new(id, breakpoint, None)
+    }
+
+    fn parse(query: &str) -> Filter {
+        let mut filter = Filter::default();
+        filter.set_query(query);
+
+        filter
+    }
+
+    #[test]
+    fn parses_structured_terms() {
+        let filter = parse("FILE:Index conn:php line:>=100 line:<5 line:7 Alice");
+
+        assert_eq!(
+            filter.terms,
+            vec![
+                Term::File("index".to_string()),
+                Term::Connector("php".to_string()),
+                Term::Line(vec![Ordering::Greater, Ordering::Equal], 100),
+                Term::Line(vec![Ordering::Less], 5),
+                Term::Line(vec![Ordering::Equal], 7),
+                Term::Text("alice".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_incomplete_terms_as_text() {
+        let filter = parse("line: line:>= line:>=x file:");
+
+        assert_eq!(
+            filter.terms,
+            vec![
+                Term::Text("line:".to_string()),
+                Term::Text("line:>=".to_string()),
+                Term::Text("line:>=x".to_string()),
+                Term::Text("file:".to_string()),
+            ]
+        );
+        assert!(parse("  ").is_empty());
+    }
+
+    #[test]
+    fn matches_every_term() {
+        let php = breakpoint(1, "/var/www/Index.php", "120", "php");
+        let rust = breakpoint(2, "src/main.rs", "7", "rust");
+
+        let mut filter = parse("file:index line:>=100");
+        assert!(filter.matches(&php));
+        assert!(!filter.matches(&rust));
+
+        filter.set_query("alice conn:RUST");
+        assert!(!filter.matches(&php));
+        assert!(filter.matches(&rust));
+
+        filter.set_query("line:<=7 main");
+        assert!(filter.matches(&rust));
+        filter.set_query("bob");
+        assert!(!filter.matches(&rust));
+    }
+
+    #[test]
+    fn matches_original_filepaths() {
+        let mut mapped = breakpoint(1, "/home/me/app/index.php", "3", "php");
+        mapped.original_filepath = Some("/var/www/index.php".to_string());
+
+        assert!(parse("file:var/www").matches(&mapped));
+        assert!(parse("file:home/me").matches(&mapped));
+        // Plain text only looks at the path the adapter sent.
+        assert!(!parse("home/me").matches(&mapped));
+    }
+
+    #[test]
+    fn forgets_removed_breakpoints() {
+        let kept = Arc::new(breakpoint(2, "b.rs", "1", "rust"));
+        let mut filter = parse("alice");
+        filter.matches(&breakpoint(1, "a.rs", "1", "rust"));
+        filter.matches(&kept);
+
+        filter.retain(&[kept]);
+        assert_eq!(filter.cache.keys().collect::<Vec<_>>(), vec![&2]);
+    }
+}